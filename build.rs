@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+
+    let mut paths = Vec::new();
+    files_in(Path::new("src"), &mut paths);
+    paths.sort();
+
+    let mut keys = Vec::new();
+    for path in paths {
+        if path.extension() != Some("rs".as_ref()) {
+            continue;
+        }
+        let s = std::fs::read_to_string(&path).expect("cannot read source file");
+        keys.extend(text_keys(&s));
+    }
+    keys.sort();
+    keys.dedup();
+
+    let mut out = String::from("&[\n");
+    for key in keys {
+        out.push_str(&format!("    {:?},\n", key));
+    }
+    out.push(']');
+
+    let out_path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("code_keys.rs");
+    std::fs::write(out_path, out).expect("cannot write code keys");
+}
+
+fn files_in(dir: &Path, paths: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("cannot read source directory") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files_in(&path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// Keys given to `t!()` as string literals
+fn text_keys(s: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut rest = s;
+    while let Some(i) = rest.find("t!(") {
+        let is_macro =
+            !matches!(rest[..i].chars().next_back(), Some(c) if c.is_alphanumeric() || c == '_');
+        rest = &rest[(i + 3)..];
+        if !is_macro {
+            continue;
+        }
+        if let Some(literal) = rest.trim_start().strip_prefix('"') {
+            if let Some(end) = literal.find('"') {
+                keys.push(literal[..end].to_owned());
+            }
+        }
+    }
+    keys
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use geom::Coords;
 
-use crate::defs::*;
+use crate::assets::AssetsLoaded;
 use crate::planet::Planet;
 use crate::screen::CursorMode;
 
@@ -24,6 +24,7 @@ fn cursor_action(
     mut er: EventReader<CursorAction>,
     cursor_mode: Res<CursorMode>,
     mut planet: ResMut<Planet>,
+    assets: Option<Res<AssetsLoaded>>,
 ) {
     for e in er.iter() {
         let CursorAction { coords, .. } = *e;
@@ -35,15 +36,16 @@ fn cursor_action(
             CursorMode::EditBiome(idx) => {
                 planet.map[coords].biome = idx;
             }
-            CursorMode::Build(kind) => match kind {
-                StructureKind::None => (),
-                StructureKind::Branch => {
-                    if matches!(planet.map[coords].structure, Structure::None) {
-                        planet.map[coords].structure = Structure::Branch;
+            CursorMode::Build(kind) => {
+                let asset = assets
+                    .as_ref()
+                    .and_then(|assets| assets.structures.get(&kind));
+                if let (Some(structure), Some(asset)) = (kind.structure(), asset) {
+                    if asset.attrs.buildable && planet.buildable(coords, &asset.attrs) {
+                        planet.build(coords, structure, &asset.attrs);
                     }
                 }
-                _ => todo!(),
-            },
+            }
         }
     }
 }
@@ -1,9 +1,16 @@
+mod atlas;
+pub mod validate;
+
+pub use atlas::PackedSheet;
+
 use crate::defs::*;
-use bevy::asset::LoadState;
+use crate::msg::{push_msg, MsgKind};
+use bevy::asset::{Asset, FileAssetIo, LoadState};
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
 use fnv::FnvHashMap;
 use serde::Deserialize;
+use std::path::PathBuf;
 
 #[derive(Clone, Copy, Debug)]
 pub struct AssetsPlugin;
@@ -18,17 +25,23 @@ impl Plugin for AssetsPlugin {
         ))
         .init_resource::<AssetsLoading>()
         .add_startup_system(load_assets)
-        .add_system(create_assets_list);
+        .add_system(create_assets_list)
+        .add_system(reload_assets);
     }
 }
 
 #[derive(Default)]
 struct AssetsLoading(Vec<HandleUntyped>);
 
+/// Handles kept after loading, to be notified of modifications
+struct LoadedHandles {
+    _handles: Vec<HandleUntyped>,
+}
+
 #[derive(Clone, Debug, Deserialize, TypeUuid)]
 #[serde(transparent)]
 #[uuid = "99d5021f-98fb-4873-b16a-bd9619b8b074"]
-pub struct BiomeAssetList(FnvHashMap<Biome, BiomeAttrs>);
+pub struct BiomeAssetList(Vec<BiomeAttrs>);
 
 #[derive(Clone, Debug, Deserialize, TypeUuid)]
 #[serde(transparent)]
@@ -36,18 +49,160 @@ pub struct BiomeAssetList(FnvHashMap<Biome, BiomeAttrs>);
 pub struct StructureAssetList(FnvHashMap<StructureKind, StructureAttrs>);
 
 pub struct AssetsLoaded {
-    pub biomes: FnvHashMap<Biome, BiomeAsset>,
+    /// Indexed by `Biome`
+    pub biomes: Vec<BiomeAsset>,
     pub structures: FnvHashMap<StructureKind, StructureAsset>,
 }
 
+impl AssetsLoaded {
+    pub fn biome_ids(&self) -> Vec<String> {
+        self.biomes
+            .iter()
+            .map(|asset| asset.attrs.id.clone())
+            .collect()
+    }
+
+    pub fn biome_colors(&self) -> Vec<(String, [u8; 3])> {
+        self.biomes
+            .iter()
+            .map(|asset| (asset.attrs.id.clone(), asset.attrs.color))
+            .collect()
+    }
+
+    pub fn structure_sizes(&self) -> FnvHashMap<StructureKind, StructureSize> {
+        self.structures
+            .iter()
+            .map(|(kind, asset)| (*kind, asset.attrs.size))
+            .collect()
+    }
+}
+
 pub struct BiomeAsset {
     pub attrs: BiomeAttrs,
-    pub texture_atlas: Handle<TextureAtlas>,
+    /// The main texture and the variants
+    pub sheets: Vec<PackedSheet>,
 }
 
 pub struct StructureAsset {
     pub attrs: StructureAttrs,
-    pub texture_atlas: Handle<TextureAtlas>,
+    pub sheet: PackedSheet,
+}
+
+/// Scales of asset sets, in texture pixels per logical pixel.
+/// Images for scale 2 are optional and named like `ocean@2x.png`.
+pub const TEXTURE_SCALES: [u32; 2] = [1, 2];
+
+/// Path of the image for the texture scale
+pub fn scaled_image_path(path: &str, scale: u32) -> String {
+    if scale == 1 {
+        return path.to_owned();
+    }
+    match path.strip_suffix(".png") {
+        Some(stem) => format!("{}@{}x.png", stem, scale),
+        None => path.to_owned(),
+    }
+}
+
+/// Largest texture scale which does not exceed the scale factor of the primary window
+fn texture_scale(windows: &Windows) -> u32 {
+    let scale_factor = windows
+        .get_primary()
+        .map_or(1.0, |window| window.scale_factor())
+        .round();
+    TEXTURE_SCALES
+        .into_iter()
+        .filter(|scale| *scale as f64 <= scale_factor)
+        .max()
+        .unwrap_or(1)
+}
+
+/// The base asset directory, which mods are layered over
+pub fn asset_dir() -> PathBuf {
+    FileAssetIo::get_root_path().join("assets")
+}
+
+/// Read the biome lists of all layers without the asset server, for command line tools
+pub fn read_biome_list() -> anyhow::Result<Vec<BiomeAttrs>> {
+    let lists: Vec<Vec<BiomeAttrs>> = read_lists("biomes", ".biomes.ron")?;
+    Ok(merge_biome_lists(lists.iter().map(|list| list.as_slice())))
+}
+
+/// Read the structure lists of all layers without the asset server, for command line tools
+pub fn read_structure_list() -> anyhow::Result<FnvHashMap<StructureKind, StructureAttrs>> {
+    let lists: Vec<FnvHashMap<StructureKind, StructureAttrs>> =
+        read_lists("structures", ".structures.ron")?;
+    Ok(merge_structure_lists(&lists))
+}
+
+fn read_lists<T: serde::de::DeserializeOwned>(dir: &str, suffix: &str) -> anyhow::Result<Vec<T>> {
+    use anyhow::Context;
+
+    crate::mods::list_files(dir, suffix)
+        .into_iter()
+        .map(|path| {
+            let path = crate::mods::resolve(path);
+            let s = std::fs::read_to_string(&path)?;
+            ron::de::from_str(&s).with_context(|| format!("invalid {}", path.display()))
+        })
+        .collect()
+}
+
+/// Merge biome lists in the order of layers.
+/// An entry with an existing id replaces the old one at its position.
+pub fn merge_biome_lists<'a>(lists: impl IntoIterator<Item = &'a [BiomeAttrs]>) -> Vec<BiomeAttrs> {
+    let mut merged: Vec<BiomeAttrs> = Vec::new();
+    for attrs in lists.into_iter().flatten() {
+        if let Some(old) = merged.iter_mut().find(|old| old.id == attrs.id) {
+            *old = attrs.clone();
+        } else {
+            merged.push(attrs.clone());
+        }
+    }
+    merged
+}
+
+/// Merge structure lists in the order of layers, later entries replacing earlier ones
+pub fn merge_structure_lists<'a>(
+    lists: impl IntoIterator<Item = &'a FnvHashMap<StructureKind, StructureAttrs>>,
+) -> FnvHashMap<StructureKind, StructureAttrs> {
+    let mut merged = FnvHashMap::default();
+    for list in lists {
+        merged.extend(list.iter().map(|(kind, attrs)| (*kind, attrs.clone())));
+    }
+    merged
+}
+
+/// Loaded assets sorted in the order of layers
+fn sorted_by_layer<'a, T: Asset>(asset_server: &AssetServer, assets: &'a Assets<T>) -> Vec<&'a T> {
+    let mut assets: Vec<(PathBuf, &T)> = assets
+        .iter()
+        .filter_map(|(id, asset)| {
+            asset_server
+                .get_handle_path(id)
+                .map(|path| (path.path().to_owned(), asset))
+        })
+        .collect();
+    crate::mods::sort_by_layer(&mut assets, |(path, _)| path.as_path());
+    assets.into_iter().map(|(_, asset)| asset).collect()
+}
+
+type Lists = (Vec<BiomeAttrs>, FnvHashMap<StructureKind, StructureAttrs>);
+
+/// Merged lists of all layers, or `None` if not loaded
+fn merged_lists(
+    asset_server: &AssetServer,
+    biomes: &Assets<BiomeAssetList>,
+    structures: &Assets<StructureAssetList>,
+) -> Option<Lists> {
+    let biomes = sorted_by_layer(asset_server, biomes);
+    let structures = sorted_by_layer(asset_server, structures);
+    if biomes.is_empty() || structures.is_empty() {
+        return None;
+    }
+    Some((
+        merge_biome_lists(biomes.iter().map(|list| list.0.as_slice())),
+        merge_structure_lists(structures.iter().map(|list| &list.0)),
+    ))
 }
 
 fn load_assets(asset_server: Res<AssetServer>, mut assets_loading: ResMut<AssetsLoading>) {
@@ -65,8 +220,9 @@ fn create_assets_list(
     loading: Option<Res<AssetsLoading>>,
     biomes: Res<Assets<BiomeAssetList>>,
     structures: Res<Assets<StructureAssetList>>,
-    images: Res<Assets<Image>>,
+    mut images: ResMut<Assets<Image>>,
     mut texture_atlas_assets: ResMut<Assets<TextureAtlas>>,
+    windows: Res<Windows>,
 ) {
     let loading = if let Some(loading) = loading {
         loading
@@ -76,67 +232,203 @@ fn create_assets_list(
 
     match asset_server.get_group_load_state(loading.0.iter().map(|h| h.id)) {
         LoadState::Failed => {
-            panic!();
+            log::error!("cannot load assets");
+            command.remove_resource::<AssetsLoading>();
+            return;
         }
         LoadState::Loaded => (),
         _ => {
             return;
         }
     }
+    command.insert_resource(LoadedHandles {
+        _handles: loading.0.clone(),
+    });
+    command.remove_resource::<AssetsLoading>();
 
-    let biomes = biomes
+    let (biomes, structures) =
+        if let Some(lists) = merged_lists(&asset_server, &biomes, &structures) {
+            lists
+        } else {
+            log::error!("biome or structure list not found");
+            return;
+        };
+
+    match build_assets_loaded(
+        &biomes,
+        &structures,
+        &mut images,
+        &mut texture_atlas_assets,
+        texture_scale(&windows),
+    ) {
+        Ok(assets_loaded) => {
+            command.insert_resource(assets_loaded);
+        }
+        Err(problems) => {
+            for problem in &problems {
+                log::error!("invalid assets: {}", problem);
+            }
+        }
+    }
+}
+
+/// Rebuild `AssetsLoaded` when the lists or images are modified,
+/// or the scale factor of the window changes.
+/// Invalid modifications are reported and the current assets are kept.
+fn reload_assets(
+    mut command: Commands,
+    asset_server: Res<AssetServer>,
+    loading: Option<Res<AssetsLoading>>,
+    mut er_biomes: EventReader<AssetEvent<BiomeAssetList>>,
+    mut er_structures: EventReader<AssetEvent<StructureAssetList>>,
+    mut er_images: EventReader<AssetEvent<Image>>,
+    mut er_scale_factor: EventReader<bevy::window::WindowScaleFactorChanged>,
+    biomes: Res<Assets<BiomeAssetList>>,
+    structures: Res<Assets<StructureAssetList>>,
+    mut images: ResMut<Assets<Image>>,
+    mut texture_atlas_assets: ResMut<Assets<TextureAtlas>>,
+    windows: Res<Windows>,
+) {
+    let biomes_modified = er_biomes
         .iter()
-        .next()
-        .expect("biomes not found")
-        .1
-         .0
+        .any(|e| matches!(e, AssetEvent::Modified { .. }));
+    let structures_modified = er_structures
         .iter()
-        .map(|(biome, attrs)| {
-            let image = images.get_handle(&format!("biomes/{}.png", AsRef::<str>::as_ref(biome)));
-            let texture_atlas =
-                TextureAtlas::from_grid(image, Vec2::new(PIECE_SIZE, PIECE_SIZE), 6, 4);
-            let texture_atlas = texture_atlas_assets.add(texture_atlas);
+        .any(|e| matches!(e, AssetEvent::Modified { .. }));
+    let images_modified = er_images.iter().any(|e| {
+        if let AssetEvent::Modified { handle } = e {
+            asset_server
+                .get_handle_path(handle)
+                .map(|path| {
+                    path.path().starts_with("biomes") || path.path().starts_with("structures")
+                })
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    });
+    let scale_factor_changed = er_scale_factor.iter().count() > 0;
+    if loading.is_some()
+        || !(biomes_modified || structures_modified || images_modified || scale_factor_changed)
+    {
+        return;
+    }
 
-            (
-                *biome,
-                BiomeAsset {
-                    attrs: attrs.clone(),
-                    texture_atlas,
-                },
-            )
+    let (biomes, structures) =
+        if let Some(lists) = merged_lists(&asset_server, &biomes, &structures) {
+            lists
+        } else {
+            return;
+        };
+
+    match build_assets_loaded(
+        &biomes,
+        &structures,
+        &mut images,
+        &mut texture_atlas_assets,
+        texture_scale(&windows),
+    ) {
+        Ok(assets_loaded) => {
+            log::info!("assets reloaded");
+            command.insert_resource(assets_loaded);
+        }
+        Err(problems) => {
+            for problem in &problems {
+                log::warn!("invalid assets: {}", problem);
+            }
+            push_msg(
+                MsgKind::Warn,
+                t!("assets-reload-failed"; problems = problems.join(", ")),
+            );
+        }
+    }
+}
+
+/// Build `AssetsLoaded` with the images of biomes and structures packed into shared atlases.
+/// Images for `texture_scale` are used where available.
+fn build_assets_loaded(
+    biomes: &[BiomeAttrs],
+    structures: &FnvHashMap<StructureKind, StructureAttrs>,
+    images: &mut Assets<Image>,
+    texture_atlas_assets: &mut Assets<TextureAtlas>,
+    texture_scale: u32,
+) -> Result<AssetsLoaded, Vec<String>> {
+    let problems = validate::check_lists(biomes, structures, |path| {
+        images.get(images.get_handle(path)).map(|image| {
+            let size = image.texture_descriptor.size;
+            (size.width, size.height)
         })
-        .collect();
+    });
+    if !problems.is_empty() {
+        return Err(problems);
+    }
 
-    let structures = structures
+    let structures: Vec<(StructureKind, &StructureAttrs)> = structures
         .iter()
-        .next()
-        .expect("structures not found")
-        .1
-         .0
+        .map(|(kind, attrs)| (*kind, attrs))
+        .collect();
+
+    let select_image = |path: String| -> (String, u32) {
+        let scaled = scaled_image_path(&path, texture_scale);
+        // A scaled image is not used if a higher layer overrides the image only at scale 1
+        if texture_scale > 1
+            && images.get(images.get_handle(scaled.as_str())).is_some()
+            && crate::mods::layer_of(&scaled) >= crate::mods::layer_of(&path)
+        {
+            (scaled, texture_scale)
+        } else {
+            (path, 1)
+        }
+    };
+
+    let biome_sheets = biomes.iter().flat_map(|attrs| {
+        attrs.texture_paths().into_iter().map(|path| {
+            let (path, scale) = select_image(path);
+            atlas::Sheet {
+                path,
+                cell_size: Vec2::new(PIECE_SIZE, PIECE_SIZE),
+                columns: 6,
+                rows: 4,
+                scale,
+            }
+        })
+    });
+    let structure_sheets = structures.iter().map(|(kind, attrs)| {
+        let (path, scale) = select_image(format!("structures/{}.png", AsRef::<str>::as_ref(kind)));
+        atlas::Sheet {
+            path,
+            cell_size: Vec2::new(attrs.width as _, attrs.height as _),
+            columns: attrs.columns,
+            rows: attrs.rows,
+            scale,
+        }
+    });
+    let sheets: Vec<atlas::Sheet> = biome_sheets.chain(structure_sheets).collect();
+    let mut packed = atlas::pack(&sheets, images, texture_atlas_assets)
+        .map_err(|e| vec![e])?
+        .into_iter();
+
+    let biomes = biomes
         .iter()
-        .map(|(structure, attrs)| {
-            let image = images.get_handle(&format!(
-                "structures/{}.png",
-                AsRef::<str>::as_ref(structure)
-            ));
-            let texture_atlas = TextureAtlas::from_grid(
-                image,
-                Vec2::new(attrs.width as _, attrs.height as _),
-                attrs.columns,
-                attrs.rows,
-            );
-            let texture_atlas = texture_atlas_assets.add(texture_atlas);
+        .map(|attrs| BiomeAsset {
+            attrs: attrs.clone(),
+            sheets: packed.by_ref().take(attrs.texture_paths().len()).collect(),
+        })
+        .collect();
 
+    let structures = structures
+        .into_iter()
+        .zip(packed)
+        .map(|((kind, attrs), sheet)| {
             (
-                *structure,
+                kind,
                 StructureAsset {
                     attrs: attrs.clone(),
-                    texture_atlas,
+                    sheet,
                 },
             )
         })
         .collect();
 
-    command.insert_resource(AssetsLoaded { biomes, structures });
-    command.remove_resource::<AssetsLoading>();
+    Ok(AssetsLoaded { biomes, structures })
 }
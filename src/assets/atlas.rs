@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use bevy::sprite::{Rect, TextureAtlasBuilder, TextureAtlasBuilderError};
+
+/// Maximum width and height of a shared atlas image
+const MAX_ATLAS_SIZE: f32 = 2048.0;
+
+/// An image split into a grid of cells
+#[derive(Clone, Debug)]
+pub struct Sheet {
+    pub path: String,
+    /// Logical size of cells in world coordinates
+    pub cell_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    /// Texture pixels per logical pixel of the image
+    pub scale: u32,
+}
+
+/// Location of a sheet in a shared atlas
+#[derive(Clone, Debug)]
+pub struct PackedSheet {
+    pub texture_atlas: Handle<TextureAtlas>,
+    /// Atlas index of the first cell. Cells follow in row-major order.
+    pub first_index: usize,
+    /// Logical size of cells, which sprites are drawn at regardless of the texture scale
+    pub cell_size: Vec2,
+}
+
+impl PackedSheet {
+    /// Sprite of the cell in the sheet
+    pub fn sprite(&self, cell: usize) -> TextureAtlasSprite {
+        TextureAtlasSprite {
+            index: self.first_index + cell,
+            custom_size: Some(self.cell_size),
+            ..default()
+        }
+    }
+}
+
+/// Pack the images of sheets into as few shared atlases as possible,
+/// so that sprites of different sheets can be batched together.
+/// Returns the locations in the same order as `sheets`.
+pub fn pack(
+    sheets: &[Sheet],
+    images: &mut Assets<Image>,
+    texture_atlas_assets: &mut Assets<TextureAtlas>,
+) -> Result<Vec<PackedSheet>, String> {
+    let mut packed: Vec<Option<PackedSheet>> = vec![None; sheets.len()];
+    let mut groups: Vec<Vec<usize>> = vec![(0..sheets.len()).collect()];
+
+    while let Some(group) = groups.pop() {
+        if group.is_empty() {
+            continue;
+        }
+
+        let mut builder =
+            TextureAtlasBuilder::default().max_size(Vec2::new(MAX_ATLAS_SIZE, MAX_ATLAS_SIZE));
+        let mut added: Vec<&str> = Vec::new();
+        for &i in &group {
+            let path = sheets[i].path.as_str();
+            if added.contains(&path) {
+                continue;
+            }
+            let handle = images.get_handle(path);
+            let image = images
+                .get(&handle)
+                .ok_or_else(|| format!("image \"{}\" is not loaded", path))?;
+            builder.add_texture(handle, image);
+            added.push(path);
+        }
+
+        let packed_images = match builder.finish(images) {
+            Ok(packed_images) => packed_images,
+            Err(TextureAtlasBuilderError::NotEnoughSpace) if group.len() > 1 => {
+                // Split into smaller atlases
+                let (a, b) = group.split_at(group.len() / 2);
+                groups.push(a.to_vec());
+                groups.push(b.to_vec());
+                continue;
+            }
+            Err(e) => {
+                return Err(format!("cannot pack \"{}\": {}", sheets[group[0]].path, e));
+            }
+        };
+
+        let mut texture_atlas =
+            TextureAtlas::new_empty(packed_images.texture.clone(), packed_images.size);
+        let mut first_indices = Vec::new();
+        for &i in &group {
+            let sheet = &sheets[i];
+            let handle = images.get_handle(sheet.path.as_str());
+            let origin = packed_images
+                .get_texture_index(&handle)
+                .map(|index| packed_images.textures[index].min)
+                .expect("packed image");
+
+            first_indices.push(texture_atlas.len());
+            let pixel_size = sheet.cell_size * sheet.scale as f32;
+            for row in 0..sheet.rows {
+                for column in 0..sheet.columns {
+                    let min = origin + pixel_size * Vec2::new(column as f32, row as f32);
+                    texture_atlas.add_texture(Rect {
+                        min,
+                        max: min + pixel_size,
+                    });
+                }
+            }
+        }
+
+        let texture_atlas = texture_atlas_assets.add(texture_atlas);
+        for (&i, first_index) in group.iter().zip(first_indices) {
+            packed[i] = Some(PackedSheet {
+                texture_atlas: texture_atlas.clone(),
+                first_index,
+                cell_size: sheets[i].cell_size,
+            });
+        }
+    }
+
+    Ok(packed.into_iter().map(Option::unwrap).collect())
+}
@@ -0,0 +1,278 @@
+use fnv::FnvHashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::defs::*;
+use crate::mods;
+use crate::text::TranslationText;
+
+/// Grid of biome images in pieces
+const BIOME_GRID: (u32, u32) = (6, 4);
+
+/// Keys given to `t!()` as literals in the Rust source files, collected by the build script
+const CODE_KEYS: &[&str] = include!(concat!(env!("OUT_DIR"), "/code_keys.rs"));
+
+/// Check that the lists have all structures, biome ids are consistent,
+/// and their images have the expected sizes.
+/// `image_size` returns the size of the image at the path in the asset directory.
+pub fn check_lists(
+    biomes: &[BiomeAttrs],
+    structures: &FnvHashMap<StructureKind, StructureAttrs>,
+    image_size: impl Fn(&str) -> Option<(u32, u32)>,
+) -> Vec<String> {
+    use strum::IntoEnumIterator;
+
+    let mut problems = Vec::new();
+    let known_biome = |id: &str| biomes.iter().any(|attrs| attrs.id == id);
+
+    if biomes.is_empty() {
+        problems.push("the biome list is empty".to_owned());
+    }
+    if biomes.len() > u16::MAX as usize {
+        problems.push(format!("too many biomes ({})", biomes.len()));
+    }
+    for (i, attrs) in biomes.iter().enumerate() {
+        if biomes[..i].iter().any(|other| other.id == attrs.id) {
+            problems.push(format!("biome \"{}\" is defined twice", attrs.id));
+        }
+        for transition in &attrs.transitions {
+            if !known_biome(&transition.to) {
+                problems.push(format!(
+                    "biome \"{}\" changes to unknown biome \"{}\"",
+                    attrs.id, transition.to
+                ));
+            }
+        }
+        if !(attrs.movement_cost > 0.0 && attrs.movement_cost.is_finite()) {
+            problems.push(format!(
+                "biome \"{}\" has invalid movement cost {}",
+                attrs.id, attrs.movement_cost
+            ));
+        }
+        let expected = (
+            BIOME_GRID.0 * PIECE_SIZE as u32,
+            BIOME_GRID.1 * PIECE_SIZE as u32,
+        );
+        for path in attrs.texture_paths() {
+            check_image(&path, expected, &image_size, &mut problems);
+        }
+    }
+
+    for kind in StructureKind::iter() {
+        if matches!(kind, StructureKind::None | StructureKind::Occupied) {
+            continue;
+        }
+        let attrs = if let Some(attrs) = structures.get(&kind) {
+            attrs
+        } else {
+            problems.push(format!(
+                "structure \"{}\" is not in the structure list",
+                kind.as_ref()
+            ));
+            continue;
+        };
+        for id in &attrs.placement.biomes {
+            if !known_biome(id) {
+                problems.push(format!(
+                    "structure \"{}\" is placed on unknown biome \"{}\"",
+                    kind.as_ref(),
+                    id
+                ));
+            }
+        }
+        if let Some(animation) = &attrs.animation {
+            let cells = attrs.columns * attrs.rows;
+            if animation.frames.unwrap_or(cells) > cells || animation.frames == Some(0) {
+                problems.push(format!(
+                    "animation of structure \"{}\" must have 1 to {} frames",
+                    kind.as_ref(),
+                    cells
+                ));
+            }
+            if !(animation.fps > 0.0 && animation.fps.is_finite()) {
+                problems.push(format!(
+                    "animation of structure \"{}\" has invalid fps {}",
+                    kind.as_ref(),
+                    animation.fps
+                ));
+            }
+        }
+        let path = format!("structures/{}.png", kind.as_ref());
+        let expected = (
+            attrs.columns as u32 * attrs.width,
+            attrs.rows as u32 * attrs.height,
+        );
+        check_image(&path, expected, &image_size, &mut problems);
+    }
+
+    problems
+}
+
+/// Check the image and its optional scaled versions, whose sizes must be multiplied by the scale
+fn check_image(
+    path: &str,
+    expected: (u32, u32),
+    image_size: impl Fn(&str) -> Option<(u32, u32)>,
+    problems: &mut Vec<String>,
+) {
+    for scale in super::TEXTURE_SCALES {
+        let scaled_path = super::scaled_image_path(path, scale);
+        let expected = (expected.0 * scale, expected.1 * scale);
+        match image_size(&scaled_path) {
+            Some(size) if size == expected => (),
+            Some(size) => problems.push(format!(
+                "{} is {}x{}, but must be {}x{}",
+                scaled_path, size.0, size.1, expected.0, expected.1
+            )),
+            None if scale == 1 => problems.push(format!("{} is missing", path)),
+            None => (),
+        }
+    }
+}
+
+/// Check all assets of the base directory and mods without the asset server
+pub fn check_assets() -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // Merged lists are checked only if all files are read
+    let biomes: Vec<Vec<BiomeAttrs>> = read_all("biomes", ".biomes.ron", &mut problems);
+    let structures: Vec<FnvHashMap<StructureKind, StructureAttrs>> =
+        read_all("structures", ".structures.ron", &mut problems);
+    if problems.is_empty() {
+        let biomes = super::merge_biome_lists(biomes.iter().map(|list| list.as_slice()));
+        let structures = super::merge_structure_lists(&structures);
+        problems.extend(check_lists(&biomes, &structures, |path| {
+            image::image_dimensions(mods::resolve(path)).ok()
+        }));
+    }
+
+    read_all::<TranslationText>("text", ".text.ron", &mut problems);
+
+    problems
+}
+
+/// Decode every image and parse every RON file of all layers,
+/// including the ones which the game does not refer to
+pub fn check_files() -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut paths = Vec::new();
+    for root in mods::asset_roots() {
+        files_in(&root, &mut paths);
+    }
+
+    for path in paths {
+        let name = path.to_string_lossy();
+        if name.ends_with(".png") {
+            if let Err(e) = image::open(&path) {
+                problems.push(format!("{}: {}", path.display(), e));
+            }
+        } else if name.ends_with(".ron")
+            && ![".biomes.ron", ".structures.ron", ".text.ron"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+        {
+            // Lists and texts are read by `check_assets`
+            read_ron::<ron::Value>(&path, &mut problems);
+        }
+    }
+
+    problems
+}
+
+fn files_in(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let read_dir = if let Ok(read_dir) = std::fs::read_dir(dir) {
+        read_dir
+    } else {
+        return;
+    };
+    for entry in read_dir.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            files_in(&path, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// Check that every language has the keys used in code,
+/// and the ones derived from enums and the lists
+pub fn check_translations() -> Vec<String> {
+    use strum::IntoEnumIterator;
+
+    let mut keys: Vec<String> = CODE_KEYS.iter().map(|key| (*key).to_owned()).collect();
+    keys.extend(Overlay::iter().map(|overlay| overlay.as_ref().to_owned()));
+    keys.extend(Season::iter().map(|season| season.as_ref().to_owned()));
+    keys.extend(DisasterKind::iter().map(|kind| format!("disaster-{}", kind.as_ref())));
+    // Invalid lists are reported by `check_assets`
+    if let Ok(biomes) = super::read_biome_list() {
+        keys.extend(biomes.into_iter().map(|attrs| attrs.id));
+    }
+    if let Ok(structures) = super::read_structure_list() {
+        for (kind, attrs) in structures {
+            keys.push(attrs.name.unwrap_or_else(|| kind.as_ref().to_owned()));
+            keys.extend(attrs.description);
+        }
+    }
+    keys.sort();
+    keys.dedup();
+
+    let mut langs: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for path in mods::list_files("text", ".text.ron") {
+        let lang = if let Some(lang) = crate::text::lang_of(&path) {
+            lang
+        } else {
+            continue;
+        };
+        let texts = langs.entry(lang).or_default();
+        if let Some(text) = read_ron::<TranslationText>(&mods::resolve(path), &mut Vec::new()) {
+            texts.extend(text.keys().map(|key| key.to_owned()));
+        }
+    }
+
+    let mut problems = Vec::new();
+    for (lang, texts) in &langs {
+        for key in &keys {
+            if !texts.contains(key) {
+                problems.push(format!(
+                    "text \"{}\" is missing in language \"{}\"",
+                    key, lang
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Read all files in the directory of all layers
+fn read_all<T: serde::de::DeserializeOwned>(
+    dir: &str,
+    suffix: &str,
+    problems: &mut Vec<String>,
+) -> Vec<T> {
+    let paths = mods::list_files(dir, suffix);
+    if paths.is_empty() {
+        problems.push(format!("no {} file in {}", suffix, dir));
+    }
+    paths
+        .into_iter()
+        .filter_map(|path| read_ron(&mods::resolve(path), problems))
+        .collect()
+}
+
+fn read_ron<T: serde::de::DeserializeOwned>(path: &Path, problems: &mut Vec<String>) -> Option<T> {
+    let s = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            problems.push(format!("cannot read {}: {}", path.display(), e));
+            return None;
+        }
+    };
+    match ron::de::from_str(&s) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            problems.push(format!("{}: {}", path.display(), e));
+            None
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use fnv::FnvHashMap;
+use std::path::PathBuf;
+
+use crate::defs::{StructureKind, StructureSize};
+use crate::diff::PlanetDiff;
+use crate::sim::{save, validate};
+
+/// Commands running without the window
+#[derive(Clone, Subcommand, Debug)]
+pub enum Command {
+    /// Create a planet from PNG images
+    ImportPng {
+        /// Biome image, whose colors are mapped to the nearest biome colors
+        biome: PathBuf,
+        /// Land feature image
+        #[clap(long)]
+        land_feature: Option<PathBuf>,
+        /// Output planet file
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// Write a layer of a planet to a PNG image
+    ExportPng {
+        /// Input planet file
+        input: PathBuf,
+        /// Output PNG file
+        #[clap(short, long)]
+        output: PathBuf,
+        /// "biome" or an overlay name
+        #[clap(long, default_value = "biome")]
+        layer: String,
+    },
+    /// Check the consistency of a planet file
+    CheckSave {
+        /// Planet file
+        input: PathBuf,
+        /// Write the repaired planet to this file
+        #[clap(long)]
+        repair: Option<PathBuf>,
+    },
+    /// Show differences between two planet files
+    Diff {
+        a: PathBuf,
+        b: PathBuf,
+        /// Show per-tile biomass differences larger than this
+        #[clap(long, default_value = "0.1")]
+        biomass_threshold: f32,
+    },
+}
+
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::ImportPng {
+            biome,
+            land_feature,
+            output,
+        } => {
+            let planet =
+                crate::map_image::import_png(&biome, land_feature.as_ref(), &biome_colors()?)
+                    .context("cannot import images")?;
+            save::save(&planet, output)?;
+        }
+        Command::ExportPng {
+            input,
+            output,
+            layer,
+        } => {
+            let layer = crate::map_image::parse_layer(&layer)?;
+            let planet =
+                save::load(&input).with_context(|| format!("cannot load {}", input.display()))?;
+            crate::map_image::export_png(&planet, output, layer, &biome_colors()?)?;
+        }
+        Command::CheckSave { input, repair } => {
+            let mut planet =
+                save::load(&input).with_context(|| format!("cannot load {}", input.display()))?;
+            let problems = validate::validate(&mut planet, &structure_sizes()?, repair.is_some())?;
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if let Some(output) = repair {
+                save::save(&planet, output)?;
+            } else if !problems.is_empty() {
+                bail!("{} problem(s) found", problems.len());
+            }
+        }
+        Command::Diff {
+            a,
+            b,
+            biomass_threshold,
+        } => {
+            let planet_a =
+                save::load(&a).with_context(|| format!("cannot load {}", a.display()))?;
+            let planet_b =
+                save::load(&b).with_context(|| format!("cannot load {}", b.display()))?;
+            let diff = PlanetDiff::new(&planet_a, &planet_b, biomass_threshold)?;
+            if diff.is_empty() {
+                println!("no differences");
+            } else {
+                print!("{}", diff);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check all assets and translation texts, including the texts used in code
+pub fn check_assets() -> Result<()> {
+    use crate::assets::validate;
+
+    let mut problems = validate::check_assets();
+    problems.extend(validate::check_files());
+
+    problems.extend(validate::check_translations());
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        bail!("{} problem(s) found", problems.len());
+    }
+    println!("no problems");
+    Ok(())
+}
+
+fn biome_colors() -> Result<Vec<(String, [u8; 3])>> {
+    Ok(crate::assets::read_biome_list()
+        .context("cannot read biome list")?
+        .into_iter()
+        .map(|attrs| (attrs.id, attrs.color))
+        .collect())
+}
+
+pub fn biome_ids() -> Result<Vec<String>> {
+    Ok(crate::assets::read_biome_list()
+        .context("cannot read biome list")?
+        .into_iter()
+        .map(|attrs| attrs.id)
+        .collect())
+}
+
+pub fn structure_sizes() -> Result<FnvHashMap<StructureKind, StructureSize>> {
+    Ok(crate::assets::read_structure_list()
+        .context("cannot read structure list")?
+        .into_iter()
+        .map(|(kind, attrs)| (kind, attrs.size))
+        .collect())
+}
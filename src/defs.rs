@@ -1,43 +1,133 @@
 use geom::Coords;
 use serde::{Deserialize, Serialize};
-use strum::{AsRefStr, EnumDiscriminants, EnumIter, EnumString};
+use strum::{AsRefStr, EnumDiscriminants, EnumIter};
 
+/// Logical size of a tile in world coordinates.
+/// Textures may have more pixels, as given by the texture scale.
 pub const TILE_SIZE: f32 = 48.0;
 pub const PIECE_SIZE: f32 = TILE_SIZE / 2.0;
 
+/// Index of a biome in the biome list
 #[derive(
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    PartialOrd,
-    Ord,
-    Hash,
-    Debug,
-    Serialize,
-    Deserialize,
-    EnumString,
-    EnumIter,
-    AsRefStr,
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug, Serialize, Deserialize,
 )]
-#[serde(rename_all = "kebab-case")]
-#[strum(serialize_all = "kebab-case")]
-pub enum Biome {
-    Ocean,
-    Mountains,
-    Desert,
-    Grassland,
-}
+#[serde(transparent)]
+pub struct Biome(pub u16);
 
-impl Default for Biome {
-    fn default() -> Self {
-        Self::Ocean
+impl Biome {
+    pub fn index(self) -> usize {
+        self.0 as usize
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BiomeAttrs {
+    /// Unique id, also used as the translation key of the name
+    pub id: String,
     pub z: f32,
+    /// Texture path in the asset directory. `biomes/<id>.png` is used if not given.
+    #[serde(default)]
+    pub texture: Option<String>,
+    /// Texture paths of variants, chosen per tile along with `texture` to break up repetition
+    #[serde(default)]
+    pub variants: Vec<String>,
+    /// Color on minimaps and thumbnails
+    pub color: [u8; 3],
+    /// Multiplier of the biomass that the tile can support
+    #[serde(default = "default_one")]
+    pub fertility: f32,
+    /// Cost for units to move across the tile
+    #[serde(default = "default_one")]
+    pub movement_cost: f32,
+    /// Water supplies humidity, changes temperature slowly and has no biomass
+    #[serde(default)]
+    pub water: bool,
+    /// Temperature [°C] added to the climate, e.g. by altitude
+    #[serde(default)]
+    pub temp_offset: f32,
+    /// Ratio of humidity carried over the tile to the next
+    #[serde(default = "default_humidity_transfer")]
+    pub humidity_transfer: f32,
+    /// Whether wildfires burn and spread on the tile
+    #[serde(default = "default_true")]
+    pub flammable: bool,
+    #[serde(default)]
+    pub climate: ClimateEnvelope,
+    #[serde(default)]
+    pub transitions: Vec<BiomeTransition>,
+}
+
+impl BiomeAttrs {
+    /// Texture path in the asset directory
+    pub fn texture_path(&self) -> String {
+        self.texture
+            .clone()
+            .unwrap_or_else(|| format!("biomes/{}.png", self.id))
+    }
+
+    /// Texture paths of the main texture and the variants
+    pub fn texture_paths(&self) -> Vec<String> {
+        std::iter::once(self.texture_path())
+            .chain(self.variants.iter().cloned())
+            .collect()
+    }
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_humidity_transfer() -> f32 {
+    0.85
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Climate where a biome can persist. Tiles out of it change to another biome
+/// whose envelope fits, unless a transition applies.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClimateEnvelope {
+    pub min_temp: Option<f32>,
+    pub max_temp: Option<f32>,
+    pub min_rainfall: Option<f32>,
+    pub max_rainfall: Option<f32>,
+}
+
+impl ClimateEnvelope {
+    pub fn contains(&self, temp: f32, rainfall: f32) -> bool {
+        in_range(temp, self.min_temp, self.max_temp)
+            && in_range(rainfall, self.min_rainfall, self.max_rainfall)
+    }
+}
+
+/// A biome changes to `to` when all the conditions continue for `duration` ticks
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BiomeTransition {
+    /// Id of the next biome
+    pub to: String,
+    pub duration: u32,
+    #[serde(default)]
+    pub min_rainfall: Option<f32>,
+    #[serde(default)]
+    pub max_rainfall: Option<f32>,
+    #[serde(default)]
+    pub min_biomass: Option<f32>,
+    #[serde(default)]
+    pub max_biomass: Option<f32>,
+}
+
+impl BiomeTransition {
+    pub fn satisfied(&self, rainfall: f32, biomass: f32) -> bool {
+        in_range(rainfall, self.min_rainfall, self.max_rainfall)
+            && in_range(biomass, self.min_biomass, self.max_biomass)
+    }
+}
+
+fn in_range(value: f32, min: Option<f32>, max: Option<f32>) -> bool {
+    min.iter().all(|min| value >= *min) && max.iter().all(|max| value <= *max)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -48,6 +138,117 @@ pub struct StructureAttrs {
     pub height: u32,
     pub columns: usize,
     pub rows: usize,
+    /// Translation key of the name. The structure id is used if not given.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Translation key of the description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Whether players can build it
+    #[serde(default = "default_true")]
+    pub buildable: bool,
+    #[serde(default)]
+    pub cost: Resources,
+    /// Resources paid per tick to keep working
+    #[serde(default)]
+    pub upkeep: Resources,
+    #[serde(default)]
+    pub production: Production,
+    #[serde(default)]
+    pub placement: Placement,
+    #[serde(default = "default_hp")]
+    pub hp: u32,
+    /// Ticks to complete building
+    #[serde(default)]
+    pub build_time: u32,
+    #[serde(default)]
+    pub animation: Option<Animation>,
+}
+
+fn default_hp() -> u32 {
+    100
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resources {
+    pub energy: f32,
+    pub material: f32,
+}
+
+impl Resources {
+    pub fn is_zero(&self) -> bool {
+        self.energy == 0.0 && self.material == 0.0
+    }
+}
+
+/// Production per tick by a working structure
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Production {
+    /// Resources needed to produce. Nothing is produced if they are short.
+    pub consumes: Resources,
+    pub produces: Resources,
+    /// Multiply produced energy by the sunlight on the tile
+    pub by_sunlight: bool,
+    /// Pollutants emitted to the tile
+    pub pollution: f32,
+}
+
+/// Conditions of tiles where a structure can be built
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Placement {
+    /// Ids of allowed biomes, or any biome if empty
+    pub biomes: Vec<String>,
+    /// Required land feature
+    pub land_feature: Option<LandFeature>,
+    /// Must be next to another structure
+    pub connected: bool,
+}
+
+/// Frame animation of a working structure, whose frames are the atlas cells in row-major order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Animation {
+    /// Number of frames. All cells are used if not given.
+    #[serde(default)]
+    pub frames: Option<usize>,
+    pub fps: f32,
+    #[serde(default)]
+    pub mode: AnimationMode,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    /// Play forward and backward alternately
+    PingPong,
+    /// Stop at the last frame
+    Once,
+}
+
+impl Animation {
+    /// Frame index at `elapsed` seconds from the start, for an atlas with `cells` cells
+    pub fn frame(&self, elapsed: f64, cells: usize) -> usize {
+        let frames = self.frames.unwrap_or(cells).min(cells).max(1);
+        let n = (elapsed.max(0.0) * self.fps as f64) as usize;
+        match self.mode {
+            AnimationMode::Loop => n % frames,
+            AnimationMode::PingPong if frames > 1 => {
+                let period = (frames - 1) * 2;
+                let i = n % period;
+                if i < frames {
+                    i
+                } else {
+                    period - i
+                }
+            }
+            AnimationMode::PingPong => 0,
+            AnimationMode::Once => n.min(frames - 1),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -73,7 +274,7 @@ impl Default for StructureSize {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum LandFeature {
     None,
     Oil,
@@ -81,6 +282,37 @@ pub enum LandFeature {
     Iron,
 }
 
+/// Tile values which can be shown over the map
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, EnumIter, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Overlay {
+    #[default]
+    None,
+    Biomass,
+    Rainfall,
+    Temperature,
+    Sunlight,
+    Pollution,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, EnumIter, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, EnumIter, AsRefStr)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum DisasterKind {
+    Meteor,
+    Volcano,
+    Wildfire,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, EnumDiscriminants)]
 #[strum_discriminants(name(StructureKind))]
 #[strum_discriminants(derive(Hash, Serialize, Deserialize, EnumIter, AsRefStr))]
@@ -97,3 +329,19 @@ pub enum Structure {
     SiliconChemModule,
     MiningModule,
 }
+
+impl StructureKind {
+    /// The structure placed for the kind. `None` and `Occupied` are not placed by themselves.
+    pub fn structure(self) -> Option<Structure> {
+        match self {
+            StructureKind::None | StructureKind::Occupied => None,
+            StructureKind::Branch => Some(Structure::Branch),
+            StructureKind::Core => Some(Structure::Core),
+            StructureKind::GathererDroneHub => Some(Structure::GathererDroneHub),
+            StructureKind::CombatDroneHub => Some(Structure::CombatDroneHub),
+            StructureKind::PhotosynthesisModule => Some(Structure::PhotosynthesisModule),
+            StructureKind::SiliconChemModule => Some(Structure::SiliconChemModule),
+            StructureKind::MiningModule => Some(Structure::MiningModule),
+        }
+    }
+}
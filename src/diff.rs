@@ -0,0 +1,140 @@
+use anyhow::{bail, Result};
+use geom::{Coords, RectIter};
+use std::fmt;
+
+use crate::defs::{Structure, StructureKind};
+use crate::planet::Planet;
+
+/// Differences between two planets of the same size
+#[derive(Clone, Debug)]
+pub struct PlanetDiff {
+    pub ticks: (u64, u64),
+    /// Changed resources and atmosphere values
+    pub values: Vec<(&'static str, f32, f32)>,
+    /// Biome ids, compared by ids since the planets may have different biome tables
+    pub biomes: Vec<(Coords, String, String)>,
+    pub structures: Vec<(Coords, StructureChange)>,
+    /// Biomass differences above the threshold
+    pub biomass: Vec<(Coords, f32, f32)>,
+}
+
+impl PlanetDiff {
+    pub fn new(a: &Planet, b: &Planet, biomass_threshold: f32) -> Result<Self> {
+        if a.map.size() != b.map.size() {
+            bail!(
+                "map sizes differ: {}x{} and {}x{}",
+                a.map.size().0,
+                a.map.size().1,
+                b.map.size().0,
+                b.map.size().1
+            );
+        }
+
+        let values = [
+            ("energy", a.player.energy, b.player.energy),
+            ("material", a.player.material, b.player.material),
+            ("co2", a.atmo.co2, b.atmo.co2),
+        ]
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .collect();
+
+        let mut diff = PlanetDiff {
+            ticks: (a.tick, b.tick),
+            values,
+            biomes: Vec::new(),
+            structures: Vec::new(),
+            biomass: Vec::new(),
+        };
+
+        let (w, h) = a.map.size();
+        for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+            let (tile_a, tile_b) = (&a.map[p], &b.map[p]);
+            let (biome_a, biome_b) = (a.biome_id(tile_a.biome), b.biome_id(tile_b.biome));
+            if biome_a != biome_b {
+                diff.biomes
+                    .push((p, biome_a.to_owned(), biome_b.to_owned()));
+            }
+
+            let (kind_a, kind_b) = (
+                structure_kind(&tile_a.structure),
+                structure_kind(&tile_b.structure),
+            );
+            let change = match (kind_a, kind_b) {
+                (None, Some(b)) => Some(StructureChange::Added(b)),
+                (Some(a), None) => Some(StructureChange::Removed(a)),
+                (Some(a), Some(b)) if a != b => Some(StructureChange::Replaced(a, b)),
+                _ => None,
+            };
+            if let Some(change) = change {
+                diff.structures.push((p, change));
+            }
+
+            if (tile_a.biomass - tile_b.biomass).abs() > biomass_threshold {
+                diff.biomass.push((p, tile_a.biomass, tile_b.biomass));
+            }
+        }
+
+        Ok(diff)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+            && self.biomes.is_empty()
+            && self.structures.is_empty()
+            && self.biomass.is_empty()
+    }
+}
+
+/// Structure added, removed, or replaced by another kind
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StructureChange {
+    Added(StructureKind),
+    Removed(StructureKind),
+    Replaced(StructureKind, StructureKind),
+}
+
+/// Kind of the structure placed on the tile, except occupied tiles
+fn structure_kind(structure: &Structure) -> Option<StructureKind> {
+    match structure {
+        Structure::None | Structure::Occupied { .. } => None,
+        _ => Some(structure.into()),
+    }
+}
+
+impl fmt::Display for PlanetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tick: {} -> {}", self.ticks.0, self.ticks.1)?;
+        for (name, a, b) in &self.values {
+            writeln!(f, "{}: {} -> {} ({:+})", name, a, b, b - a)?;
+        }
+
+        if !self.biomes.is_empty() {
+            writeln!(f, "biome changes ({}):", self.biomes.len())?;
+            for (p, a, b) in &self.biomes {
+                writeln!(f, "  {}: {} -> {}", p, a, b)?;
+            }
+        }
+
+        if !self.structures.is_empty() {
+            writeln!(f, "structure changes ({}):", self.structures.len())?;
+            for (p, change) in &self.structures {
+                match change {
+                    StructureChange::Added(b) => writeln!(f, "  {}: + {}", p, b.as_ref())?,
+                    StructureChange::Removed(a) => writeln!(f, "  {}: - {}", p, a.as_ref())?,
+                    StructureChange::Replaced(a, b) => {
+                        writeln!(f, "  {}: {} -> {}", p, a.as_ref(), b.as_ref())?
+                    }
+                }
+            }
+        }
+
+        if !self.biomass.is_empty() {
+            writeln!(f, "biomass differences ({}):", self.biomass.len())?;
+            for (p, a, b) in &self.biomass {
+                writeln!(f, "  {}: {:.3} -> {:.3} ({:+.3})", p, a, b, b - a)?;
+            }
+        }
+        Ok(())
+    }
+}
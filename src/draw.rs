@@ -4,6 +4,7 @@ use crate::planet::*;
 use crate::screen::InScreenTileRange;
 use arrayvec::ArrayVec;
 use bevy::{core::FixedTimestep, prelude::*};
+use fnv::FnvHashMap;
 use geom::{Array2d, Coords, Direction, RectIter};
 
 #[derive(Clone, Copy, Debug)]
@@ -15,9 +16,17 @@ const CORNERS: [Coords; 4] = [Coords(-1, -1), Coords(-1, 1), Coords(1, 1), Coord
 
 const CORNER_PIECE_GRID: [(usize, usize); 4] = [(0, 1), (0, 0), (1, 0), (1, 1)];
 
+/// Alpha of the shade on the night side
+const NIGHT_ALPHA: f32 = 0.6;
+/// Sunlight under which tiles begin to get dark
+const TWILIGHT_SUNLIGHT: f32 = 0.2;
+/// Alpha of overlay colors
+const OVERLAY_ALPHA: f32 = 0.5;
+
 impl Plugin for DrawPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(initial_tile_world)
+        app.init_resource::<AnimationClock>()
+            .add_system(update_animation_clock.before("draw"))
             .add_system(update_layered_tex_map.label("draw"))
             .add_system_set(
                 SystemSet::new()
@@ -28,18 +37,65 @@ impl Plugin for DrawPlugin {
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::steps_per_second(DRAW_FPS))
                     .with_system(spawn_structure_textures.label("draw")),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(DRAW_FPS))
+                    .with_system(spawn_night_textures.label("draw")),
+            )
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(FixedTimestep::steps_per_second(DRAW_FPS))
+                    .with_system(spawn_overlay_textures.label("draw")),
             );
     }
 }
 
-pub struct LayeredTexMap {
-    biome: Array2d<ArrayVec<Biome, 9>>,
+/// Clock of structure animations, which advances every frame apart from redrawing
+#[derive(Default)]
+pub struct AnimationClock {
+    /// Seconds since the start
+    elapsed: f64,
+    /// Time when the structure based on the position started working
+    started: FnvHashMap<(i32, i32), (StructureKind, f64)>,
 }
 
-fn initial_tile_world(mut commands: Commands) {
-    let planet = Planet::new(30, 30);
+impl AnimationClock {
+    /// Seconds since the structure based on `p` started working
+    fn working_time(&self, p: Coords) -> f64 {
+        self.started
+            .get(&(p.0, p.1))
+            .map(|(_, start)| self.elapsed - start)
+            .unwrap_or(0.0)
+    }
+}
 
-    commands.insert_resource(planet);
+fn update_animation_clock(time: Res<Time>, planet: Res<Planet>, mut clock: ResMut<AnimationClock>) {
+    clock.elapsed += time.delta_seconds_f64();
+    if !planet.is_changed() {
+        return;
+    }
+
+    let elapsed = clock.elapsed;
+    let (w, h) = planet.map.size();
+    let mut started = FnvHashMap::default();
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &planet.map[p];
+        if matches!(tile.structure, Structure::None | Structure::Occupied { .. }) {
+            continue;
+        }
+        let kind = StructureKind::from(&tile.structure);
+        let start = match clock.started.get(&(p.0, p.1)) {
+            Some((old_kind, start)) if *old_kind == kind && tile.build_ticks == 0 => *start,
+            _ => elapsed,
+        };
+        started.insert((p.0, p.1), (kind, start));
+    }
+    clock.started = started;
+}
+
+pub struct LayeredTexMap {
+    biome: Array2d<ArrayVec<Biome, 9>>,
 }
 
 fn update_layered_tex_map(
@@ -48,32 +104,37 @@ fn update_layered_tex_map(
     planet: Res<Planet>,
     ltm: Option<ResMut<LayeredTexMap>>,
 ) {
-    if !planet.is_changed() && ltm.is_some() {
-        return;
-    }
     let assets = if let Some(assets) = &assets {
         assets
     } else {
         return;
     };
+    if !planet.is_changed() && !assets.is_changed() && ltm.is_some() {
+        return;
+    }
 
     let (w, h) = planet.map.size();
     let mut tiles = Array2d::new(w, h, ArrayVec::new());
 
-    for &i in assets.biomes.keys() {
+    for (i, asset) in assets.biomes.iter().enumerate() {
+        let i = Biome(i as u16);
         for pos in RectIter::new((0, 0), (w - 1, h - 1)) {
             let biome_i = planet.map[pos].biome;
             if biome_i != i {
                 continue;
             }
 
-            let tile_z = assets.biomes[&biome_i].attrs.z;
+            let tile_z = asset.attrs.z;
             tiles[pos].push(i);
             for d in Direction::EIGHT_DIRS {
                 let p = pos + d.as_coords();
                 if tiles.in_range(p) {
                     let surround_tile_i = planet.map[p].biome;
-                    let z = assets.biomes[&surround_tile_i].attrs.z;
+                    let z = if let Some(asset) = assets.biomes.get(surround_tile_i.index()) {
+                        asset.attrs.z
+                    } else {
+                        continue;
+                    };
                     if z < tile_z && !tiles[pos].contains(&surround_tile_i) {
                         tiles[pos].push(surround_tile_i);
                     }
@@ -125,8 +186,6 @@ fn spawn_map_textures(
 
                 let index = grid_x + grid_y * 6;
 
-                let sprite = TextureAtlasSprite { index, ..default() };
-
                 let x = p.0 as f32 * TILE_SIZE
                     + PIECE_SIZE * ((corner.0 + 1) / 2) as f32
                     + PIECE_SIZE / 2.0;
@@ -134,10 +193,17 @@ fn spawn_map_textures(
                     + PIECE_SIZE * ((corner.1 + 1) / 2) as f32
                     + PIECE_SIZE / 2.0;
 
-                let tile_asset = &assets.biomes[tile_idx];
+                let tile_asset = if let Some(tile_asset) = assets.biomes.get(tile_idx.index()) {
+                    tile_asset
+                } else {
+                    continue;
+                };
+                let sheets = &tile_asset.sheets;
+                let sheet = &sheets[tile_hash(p) as usize % sheets.len()];
+                let sprite = sheet.sprite(index);
                 let id = commands
                     .spawn_bundle(SpriteSheetBundle {
-                        texture_atlas: tile_asset.texture_atlas.clone(),
+                        texture_atlas: sheet.texture_atlas.clone(),
                         sprite,
                         transform: Transform::from_xyz(x, y, tile_asset.attrs.z / 10.0),
                         visibility: Visibility { is_visible: true },
@@ -150,11 +216,20 @@ fn spawn_map_textures(
     }
 }
 
+/// Deterministic hash of tile coordinates, to choose texture variants
+fn tile_hash(p: Coords) -> u32 {
+    let mut h = (p.0 as u32).wrapping_mul(0x9e37_79b1) ^ (p.1 as u32).wrapping_mul(0x85eb_ca77);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^ (h >> 12)
+}
+
 fn spawn_structure_textures(
     mut commands: Commands,
     assets: Option<Res<AssetsLoaded>>,
     in_screen_tile_range: ResMut<InScreenTileRange>,
     planet: Res<Planet>,
+    clock: Res<AnimationClock>,
     mut tex_entities: Local<Vec<Entity>>,
 ) {
     let assets = if let Some(assets) = &assets {
@@ -189,8 +264,6 @@ fn spawn_structure_textures(
 
                 let index = grid_x + grid_y * 6;
 
-                let sprite = TextureAtlasSprite { index, ..default() };
-
                 let x = p.0 as f32 * TILE_SIZE
                     + PIECE_SIZE * ((corner.0 + 1) / 2) as f32
                     + PIECE_SIZE / 2.0;
@@ -198,11 +271,16 @@ fn spawn_structure_textures(
                     + PIECE_SIZE * ((corner.1 + 1) / 2) as f32
                     + PIECE_SIZE / 2.0;
 
-                let asset = &assets.structures[&StructureKind::Branch];
+                let asset = if let Some(asset) = assets.structures.get(&StructureKind::Branch) {
+                    asset
+                } else {
+                    continue;
+                };
 
+                let sprite = asset.sheet.sprite(index);
                 let id = commands
                     .spawn_bundle(SpriteSheetBundle {
-                        texture_atlas: asset.texture_atlas.clone(),
+                        texture_atlas: asset.sheet.texture_atlas.clone(),
                         sprite,
                         transform: Transform::from_xyz(x, y, 100.0),
                         visibility: Visibility { is_visible: true },
@@ -218,16 +296,24 @@ fn spawn_structure_textures(
             Structure::None | Structure::Occupied { .. } | Structure::Branch
         ) {
             let kind: StructureKind = structure.into();
-            let asset = &assets.structures[&kind];
-            let sprite = TextureAtlasSprite {
-                index: 0,
-                ..default()
+            let asset = if let Some(asset) = assets.structures.get(&kind) {
+                asset
+            } else {
+                continue;
             };
+            let index = match &asset.attrs.animation {
+                Some(animation) if planet.map[p].build_ticks == 0 => animation.frame(
+                    clock.working_time(p),
+                    asset.attrs.columns * asset.attrs.rows,
+                ),
+                _ => 0,
+            };
+            let sprite = asset.sheet.sprite(index);
             let x = p.0 as f32 * TILE_SIZE + asset.attrs.width as f32 / 2.0;
             let y = p.1 as f32 * TILE_SIZE + asset.attrs.height as f32 / 2.0;
             let id = commands
                 .spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: asset.texture_atlas.clone(),
+                    texture_atlas: asset.sheet.texture_atlas.clone(),
                     sprite,
                     transform: Transform::from_xyz(x, y, 200.0),
                     visibility: Visibility { is_visible: true },
@@ -239,6 +325,84 @@ fn spawn_structure_textures(
     }
 }
 
+fn spawn_night_textures(
+    mut commands: Commands,
+    in_screen_tile_range: ResMut<InScreenTileRange>,
+    planet: Res<Planet>,
+    mut tex_entities: Local<Vec<Entity>>,
+) {
+    for entity in tex_entities.iter() {
+        commands.entity(*entity).despawn();
+    }
+    tex_entities.clear();
+
+    for p in RectIter::new(in_screen_tile_range.from, in_screen_tile_range.to) {
+        let darkness = 1.0 - (planet.map[p].sunlight / TWILIGHT_SUNLIGHT).min(1.0);
+        if darkness <= 0.0 {
+            continue;
+        }
+
+        let x = p.0 as f32 * TILE_SIZE + TILE_SIZE / 2.0;
+        let y = p.1 as f32 * TILE_SIZE + TILE_SIZE / 2.0;
+        let id = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.0, 0.0, 0.0, NIGHT_ALPHA * darkness),
+                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, y, 300.0),
+                ..default()
+            })
+            .id();
+        tex_entities.push(id);
+    }
+}
+
+fn spawn_overlay_textures(
+    mut commands: Commands,
+    in_screen_tile_range: ResMut<InScreenTileRange>,
+    planet: Res<Planet>,
+    overlay: Res<Overlay>,
+    mut tex_entities: Local<Vec<Entity>>,
+) {
+    for entity in tex_entities.iter() {
+        commands.entity(*entity).despawn();
+    }
+    tex_entities.clear();
+
+    for p in RectIter::new(in_screen_tile_range.from, in_screen_tile_range.to) {
+        let value = if let Some(value) = planet.map[p].overlay_value(*overlay) {
+            value
+        } else {
+            continue;
+        };
+
+        let mut color = overlay_color(value);
+        color.set_a(OVERLAY_ALPHA);
+
+        let x = p.0 as f32 * TILE_SIZE + TILE_SIZE / 2.0;
+        let y = p.1 as f32 * TILE_SIZE + TILE_SIZE / 2.0;
+        let id = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(x, y, 310.0),
+                ..default()
+            })
+            .id();
+        tex_entities.push(id);
+    }
+}
+
+/// Color from blue (0.0) to red (1.0)
+pub fn overlay_color(value: f32) -> Color {
+    Color::rgb(value, 0.2, 1.0 - value)
+}
+
 fn corner_idx<F: Fn(Coords) -> bool>(f: F, pos: Coords, corner: Coords) -> usize {
     let a = f(pos + (corner.0, 0));
     let b = f(pos + (0, corner.1));
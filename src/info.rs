@@ -1,14 +1,27 @@
-use crate::defs::Structure;
+use crate::defs::{StructureAttrs, StructureKind};
+use crate::planet::Tile;
 
-pub fn structure_info(structure: &Structure) -> String {
-    match structure {
-        Structure::None | Structure::Occupied { .. } => unreachable!(),
-        Structure::Branch => {
-            t!("branch")
-        }
-        Structure::Core => {
-            t!("core")
-        }
-        _ => todo!(),
+/// Translated name of the structure
+pub fn structure_name(kind: StructureKind, attrs: &StructureAttrs) -> String {
+    t!(attrs.name.as_deref().unwrap_or_else(|| kind.as_ref()))
+}
+
+/// Information about the structure based on the tile
+pub fn structure_info(kind: StructureKind, attrs: &StructureAttrs, tile: &Tile) -> String {
+    let mut s = structure_name(kind, attrs);
+    if let Some(description) = &attrs.description {
+        s.push('\n');
+        s.push_str(&t!(description));
+    }
+    s.push_str(&format!(
+        "\n{}: {}/{}",
+        t!("hp"),
+        attrs.hp.saturating_sub(tile.damage),
+        attrs.hp
+    ));
+    if tile.build_ticks > 0 {
+        s.push('\n');
+        s.push_str(&t!("building"; ticks = tile.build_ticks));
     }
+    s
 }
@@ -2,7 +2,9 @@
 
 extern crate tile_geom as geom;
 
+use anyhow::{bail, Context};
 use clap::Parser;
+use rand::SeedableRng;
 
 #[macro_use]
 mod text;
@@ -11,29 +13,121 @@ mod msg;
 
 mod action;
 mod assets;
+mod cli;
 mod defs;
+mod diff;
 mod draw;
 mod info;
+mod map_image;
+mod mods;
 mod planet;
 mod screen;
 mod sim;
 mod ui;
 
-use bevy::{prelude::*, window::PresentMode, winit::WinitSettings};
+use bevy::{asset::AssetServerSettings, prelude::*, window::PresentMode, winit::WinitSettings};
 
 const APP_NAME: &str = concat!("Gaia Eater ", env!("CARGO_PKG_VERSION"));
 
 #[derive(Clone, Parser, Debug)]
 #[clap(author, version)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<cli::Command>,
+    /// Mod directory layered over the base assets, can be given multiple times.
+    /// Mods listed in mods.ron are used if not given.
+    #[clap(long = "mod", multiple_occurrences = true)]
+    mods: Vec<std::path::PathBuf>,
+    /// Start with the planet loaded from the file
+    #[clap(long)]
+    load: Option<std::path::PathBuf>,
+    /// Start with a new planet of the size, such as "30x30"
+    #[clap(long, parse(try_from_str = parse_map_size), conflicts_with = "load")]
+    new: Option<(u32, u32)>,
+    /// Seed for random events of the planet
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Language code of texts, such as "en"
+    #[clap(long)]
+    lang: Option<String>,
     /// Open map editing tools
     #[clap(long)]
     edit_map: bool,
+    /// Directory for save files
+    #[clap(long, default_value = "saves")]
+    save_dir: std::path::PathBuf,
+    /// Autosave interval in ticks, 0 to disable
+    #[clap(long, default_value = "150")]
+    autosave_ticks: u64,
+    /// Autosave interval in minutes, 0 to disable
+    #[clap(long, default_value = "0")]
+    autosave_minutes: f64,
+    /// Number of rotating autosave slots
+    #[clap(long, default_value = "3")]
+    autosave_slots: usize,
+    /// Repair broken planets on loading instead of rejecting them
+    #[clap(long)]
+    repair_saves: bool,
+    /// Reload modified assets while running, not available with mods
+    #[clap(long)]
+    watch_assets: bool,
+    /// Check all assets and translation texts without the window, then exit
+    #[clap(long)]
+    check_assets: bool,
 }
 
 fn main() {
     let args = Args::parse();
 
+    if let Err(e) = mods::init(&args.mods) {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+    if args.watch_assets && !mods::active().is_empty() {
+        eprintln!("error: --watch-assets cannot be used with mods");
+        std::process::exit(1);
+    }
+
+    if args.check_assets {
+        if let Err(e) = cli::check_assets() {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(command) = args.command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let problems = assets::validate::check_assets();
+    if !problems.is_empty() {
+        eprintln!("error: invalid assets");
+        for problem in problems {
+            eprintln!("  {}", problem);
+        }
+        std::process::exit(1);
+    }
+
+    if let Some(lang) = &args.lang {
+        if let Err(e) = text::set_lang_code(lang) {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let planet = match initial_planet(&args) {
+        Ok(planet) => planet,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
     App::new()
         .insert_resource(DefaultTaskPoolOptions::with_num_threads(2))
         .insert_resource(WindowDescriptor {
@@ -41,7 +135,14 @@ fn main() {
             present_mode: PresentMode::Mailbox,
             ..Default::default()
         })
-        .add_plugins(DefaultPlugins)
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: args.watch_assets,
+            ..default()
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.add_before::<bevy::asset::AssetPlugin, _>(mods::ModsPlugin)
+        })
+        .insert_resource(planet)
         .add_plugin(text::TextPlugin)
         .add_plugin(assets::AssetsPlugin)
         .add_plugin(screen::ScreenPlugin)
@@ -51,11 +152,60 @@ fn main() {
         .add_plugin(InspectorPlugin)
         .add_plugin(draw::DrawPlugin)
         .add_plugin(action::ActionPlugin)
-        .add_plugin(sim::SimPlugin)
+        .add_plugin(sim::SimPlugin {
+            save_conf: sim::SaveConf {
+                dir: args.save_dir,
+                autosave_ticks: args.autosave_ticks,
+                autosave_minutes: args.autosave_minutes,
+                autosave_slots: args.autosave_slots,
+                repair: args.repair_saves,
+            },
+        })
         .insert_resource(WinitSettings::game())
         .run();
 }
 
+fn parse_map_size(s: &str) -> anyhow::Result<(u32, u32)> {
+    let (w, h) = s
+        .split_once('x')
+        .context("map size must be given as <width>x<height>")?;
+    let (w, h): (u32, u32) = (w.parse()?, h.parse()?);
+    if w < planet::MIN_MAP_SIZE || h < planet::MIN_MAP_SIZE {
+        bail!("map size {}x{} is too small", w, h);
+    }
+    Ok((w, h))
+}
+
+fn initial_planet(args: &Args) -> anyhow::Result<planet::Planet> {
+    let mut planet = if let Some(path) = &args.load {
+        let (mut planet, problems) = sim::validate::load_checked(
+            path,
+            &cli::structure_sizes()?,
+            &cli::biome_ids()?,
+            args.repair_saves,
+        )
+        .with_context(|| format!("cannot load {}", path.display()))?;
+        for problem in problems {
+            eprintln!("warning: repaired: {}", problem);
+        }
+        if let Some(differences) = mods::adopt(&mut planet.mods) {
+            eprintln!(
+                "warning: mods differ from the saved planet: {}",
+                differences
+            );
+        }
+        planet
+    } else {
+        let (w, h) = args.new.unwrap_or((30, 30));
+        planet::Planet::new(w, h, cli::biome_ids()?)
+    };
+
+    if let Some(seed) = args.seed {
+        planet.rng = planet::PlanetRng::seed_from_u64(seed);
+    }
+    Ok(planet)
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct InspectorPlugin;
 
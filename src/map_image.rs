@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use geom::Coords;
+use std::path::Path;
+
+use crate::defs::{Biome, LandFeature, Overlay};
+use crate::draw::overlay_color;
+use crate::planet::{Planet, MIN_MAP_SIZE};
+
+/// Biome ids and colors in the order of the biome list
+pub type BiomeColors = [(String, [u8; 3])];
+
+/// Colors of land features in feature images. Other colors mean no feature.
+const LAND_FEATURE_COLORS: &[(LandFeature, [u8; 3])] = &[
+    (LandFeature::Oil, [0, 0, 0]),
+    (LandFeature::Lime, [255, 255, 255]),
+    (LandFeature::Iron, [200, 60, 40]),
+];
+
+/// Pixel colors of a layer, from the top row to the bottom row.
+/// `Overlay::None` means the biome layer.
+pub fn layer_pixels(planet: &Planet, layer: Overlay, biome_colors: &BiomeColors) -> Vec<[u8; 3]> {
+    let (w, h) = planet.map.size();
+    // Colors indexed by the biome of the planet, which may not follow the biome list
+    let colors: Vec<[u8; 3]> = planet
+        .biome_ids
+        .iter()
+        .map(|id| {
+            biome_colors
+                .iter()
+                .find(|(biome_id, _)| biome_id == id)
+                .map(|(_, color)| *color)
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut pixels = Vec::with_capacity((w * h) as usize);
+
+    // Tile y increases upward on the screen
+    for y in (0..h as i32).rev() {
+        for x in 0..w as i32 {
+            let tile = &planet.map[(x, y)];
+            let color = if let Some(value) = tile.overlay_value(layer) {
+                let [r, g, b, _] = overlay_color(value).as_rgba_f32();
+                [r, g, b].map(|c| (c * 255.0) as u8)
+            } else {
+                colors.get(tile.biome.index()).copied().unwrap_or_default()
+            };
+            pixels.push(color);
+        }
+    }
+    pixels
+}
+
+pub fn export_png<P: AsRef<Path>>(
+    planet: &Planet,
+    path: P,
+    layer: Overlay,
+    biome_colors: &BiomeColors,
+) -> Result<()> {
+    let (w, h) = planet.map.size();
+    let pixels = layer_pixels(planet, layer, biome_colors);
+    image::save_buffer(path, &pixels.concat(), w, h, image::ColorType::Rgb8)?;
+    Ok(())
+}
+
+/// Create a planet from a biome image, mapping each pixel to the biome with the nearest color,
+/// and an optional land feature image
+pub fn import_png<P: AsRef<Path>>(
+    biome_path: P,
+    land_feature_path: Option<P>,
+    biome_colors: &BiomeColors,
+) -> Result<Planet> {
+    let biome_image = image::open(biome_path)?.into_rgb8();
+    let (w, h) = biome_image.dimensions();
+    if w < MIN_MAP_SIZE || h < MIN_MAP_SIZE {
+        bail!("image size {}x{} is too small", w, h);
+    }
+    if biome_colors.is_empty() {
+        bail!("no biome colors");
+    }
+
+    let land_feature_image = if let Some(path) = land_feature_path {
+        let image = image::open(path)?.into_rgb8();
+        if image.dimensions() != (w, h) {
+            bail!(
+                "land feature image size {:?} differs from biome image size {:?}",
+                image.dimensions(),
+                (w, h)
+            );
+        }
+        Some(image)
+    } else {
+        None
+    };
+
+    let biome_ids = biome_colors.iter().map(|(id, _)| id.clone()).collect();
+    let mut planet = Planet::new(w, h, biome_ids);
+
+    for (x, y_image, pixel) in biome_image.enumerate_pixels() {
+        let p = Coords(x as i32, (h - 1 - y_image) as i32);
+        let biome = biome_colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (_, color))| color_distance(pixel.0, *color))
+            .map(|(i, _)| Biome(i as u16))
+            .unwrap();
+        planet.map[p].biome = biome;
+
+        if let Some(image) = &land_feature_image {
+            let color = image.get_pixel(x, y_image).0;
+            if let Some((land_feature, _)) = LAND_FEATURE_COLORS.iter().find(|(_, c)| *c == color) {
+                planet.map[p].land_feature = land_feature.clone();
+            }
+        }
+    }
+
+    Ok(planet)
+}
+
+fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Parse a layer name, where "biome" means the biome layer
+pub fn parse_layer(s: &str) -> Result<Overlay> {
+    use strum::IntoEnumIterator;
+
+    if s == "biome" {
+        return Ok(Overlay::None);
+    }
+    Overlay::iter()
+        .find(|overlay| *overlay != Overlay::None && overlay.as_ref() == s)
+        .ok_or_else(|| anyhow::anyhow!("unknown layer \"{}\"", s))
+}
@@ -0,0 +1,254 @@
+mod asset_io;
+
+pub use asset_io::LayeredAssetIo;
+
+use anyhow::{bail, Context, Result};
+use bevy::asset::FileAssetIo;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File at the root of a mod directory
+const MANIFEST_FILE: &str = "mod.ron";
+/// Config file listing mod directories, used if no mod is given on the command line
+const CONFIG_FILE: &str = "mods.ron";
+/// Directories and name suffixes of list files, whose entries are merged across layers
+const LIST_FILES: &[(&str, &str)] = &[
+    ("biomes", ".biomes.ron"),
+    ("structures", ".structures.ron"),
+    ("text", ".text.ron"),
+];
+
+static MODS: OnceCell<Vec<Mod>> = OnceCell::new();
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A directory of assets layered over the base asset directory
+#[derive(Clone, Debug)]
+pub struct Mod {
+    pub manifest: ModManifest,
+    pub dir: PathBuf,
+}
+
+/// Mod name and version recorded in planets
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ModInfo {
+    pub name: String,
+    pub version: String,
+}
+
+/// Installs `LayeredAssetIo` if any mod is active. Must be added before `AssetPlugin`.
+/// Watching assets for changes is not supported with it.
+#[derive(Clone, Copy, Debug)]
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        if active().is_empty() {
+            return;
+        }
+        for m in active() {
+            log::info!(
+                "mod {} {} is active: {}",
+                m.manifest.name,
+                m.manifest.version,
+                m.manifest.description
+            );
+        }
+
+        let task_pool = app.world.resource::<IoTaskPool>().0.clone();
+        let asset_io = LayeredAssetIo::new(asset_roots());
+        app.insert_resource(AssetServer::new(asset_io, task_pool));
+    }
+}
+
+/// Activate mods in the directories, or the ones listed in the config file if empty.
+/// Must be called before loading any asset.
+pub fn init(dirs: &[PathBuf]) -> Result<()> {
+    let dirs = if dirs.is_empty() {
+        read_config()?
+    } else {
+        dirs.to_vec()
+    };
+
+    let mut mods: Vec<Mod> = Vec::new();
+    for dir in dirs {
+        let path = dir.join(MANIFEST_FILE);
+        let s = std::fs::read_to_string(&path)
+            .with_context(|| format!("cannot read mod manifest {}", path.display()))?;
+        let manifest: ModManifest =
+            ron::de::from_str(&s).with_context(|| format!("invalid {}", path.display()))?;
+        if mods.iter().any(|m| m.manifest.name == manifest.name) {
+            bail!("mod \"{}\" is given twice", manifest.name);
+        }
+        let lower_roots: Vec<PathBuf> = std::iter::once(crate::assets::asset_dir())
+            .chain(mods.iter().map(|m| m.dir.clone()))
+            .collect();
+        check_list_names(&manifest.name, &dir, &lower_roots)?;
+        mods.push(Mod { manifest, dir });
+    }
+
+    MODS.set(mods)
+        .map_err(|_| anyhow::anyhow!("mods are already initialized"))
+}
+
+/// List files are merged only if their asset paths differ,
+/// so a file with the same path as a lower layer's one would replace the whole list.
+fn check_list_names(name: &str, dir: &Path, lower_roots: &[PathBuf]) -> Result<()> {
+    for (list_dir, suffix) in LIST_FILES {
+        let read_dir = if let Ok(read_dir) = std::fs::read_dir(dir.join(list_dir)) {
+            read_dir
+        } else {
+            continue;
+        };
+        for entry in read_dir.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name();
+            if !file_name.to_string_lossy().ends_with(suffix) {
+                continue;
+            }
+            let path = Path::new(list_dir).join(&file_name);
+            if lower_roots.iter().any(|root| root.join(&path).exists()) {
+                bail!(
+                    "mod \"{}\" has {}, which would replace the whole list of a lower layer. \
+                     Rename it so that its entries are merged, e.g. to \"{}\"",
+                    name,
+                    path.display(),
+                    merged_list_name(name, &path, suffix)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Name of a list file in the mod that is merged with the list at `path`
+fn merged_list_name(name: &str, path: &Path, suffix: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    match crate::text::lang_of(path) {
+        Some(lang) if suffix == ".text.ron" => format!("{}.{}{}", lang, name, suffix),
+        _ => format!("{}{}", name, suffix),
+    }
+}
+
+fn read_config() -> Result<Vec<PathBuf>> {
+    let root = FileAssetIo::get_root_path();
+    let path = root.join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let s = std::fs::read_to_string(&path)?;
+    let dirs: Vec<PathBuf> =
+        ron::de::from_str(&s).with_context(|| format!("invalid {}", path.display()))?;
+    Ok(dirs.into_iter().map(|dir| root.join(dir)).collect())
+}
+
+pub fn active() -> &'static [Mod] {
+    MODS.get().map(|mods| mods.as_slice()).unwrap_or(&[])
+}
+
+pub fn active_infos() -> Vec<ModInfo> {
+    active()
+        .iter()
+        .map(|m| ModInfo {
+            name: m.manifest.name.clone(),
+            version: m.manifest.version.clone(),
+        })
+        .collect()
+}
+
+/// Record the active mods in the planet.
+/// Returns a description of the differences from the mods which it was saved with.
+pub fn adopt(mods: &mut Vec<ModInfo>) -> Option<String> {
+    let active = active_infos();
+    let mut differences = Vec::new();
+    for m in mods.iter() {
+        match active.iter().find(|a| a.name == m.name) {
+            None => differences.push(format!("-{} {}", m.name, m.version)),
+            Some(a) if a.version != m.version => {
+                differences.push(format!("{} {} -> {}", m.name, m.version, a.version))
+            }
+            _ => (),
+        }
+    }
+    for a in &active {
+        if !mods.iter().any(|m| m.name == a.name) {
+            differences.push(format!("+{} {}", a.name, a.version));
+        }
+    }
+
+    *mods = active;
+    if differences.is_empty() {
+        None
+    } else {
+        Some(differences.join(", "))
+    }
+}
+
+/// Asset directories from the lowest priority, so the base directory is the first
+pub fn asset_roots() -> Vec<PathBuf> {
+    std::iter::once(crate::assets::asset_dir())
+        .chain(active().iter().map(|m| m.dir.clone()))
+        .collect()
+}
+
+/// Index in `asset_roots` of the highest priority directory which has the asset path
+pub fn layer_of<P: AsRef<Path>>(path: P) -> usize {
+    asset_roots()
+        .iter()
+        .rposition(|root| root.join(path.as_ref()).exists())
+        .unwrap_or(0)
+}
+
+/// Actual path of the asset path, in the highest priority directory which has it
+pub fn resolve<P: AsRef<Path>>(path: P) -> PathBuf {
+    let roots = asset_roots();
+    roots[layer_of(path.as_ref())].join(path)
+}
+
+/// Asset paths of the files in the directory of all layers, whose names end with `suffix`.
+/// Sorted by the layer and the name, which is the order to merge lists.
+/// A path in several layers is listed once, so mods may not reuse names of list files.
+pub fn list_files<P: AsRef<Path>>(dir: P, suffix: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for root in asset_roots() {
+        let read_dir = if let Ok(read_dir) = std::fs::read_dir(root.join(dir.as_ref())) {
+            read_dir
+        } else {
+            continue;
+        };
+        for entry in read_dir.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name();
+            if !name.to_string_lossy().ends_with(suffix) {
+                continue;
+            }
+            let path = dir.as_ref().join(name);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    sort_by_layer(&mut paths, |path| path.as_path());
+    paths
+}
+
+/// Sort items by the layer and the name of their asset paths
+pub fn sort_by_layer<T>(items: &mut [T], path: impl Fn(&T) -> &Path) {
+    items.sort_by_cached_key(|item| (layer_of(path(item)), path(item).to_owned()));
+}
@@ -0,0 +1,71 @@
+use bevy::asset::{AssetIo, AssetIoError};
+use bevy::utils::BoxedFuture;
+use std::path::{Path, PathBuf};
+
+/// Reads assets from the highest priority directory that has them,
+/// and lists directories as the union of all directories
+pub struct LayeredAssetIo {
+    /// From the lowest priority
+    roots: Vec<PathBuf>,
+}
+
+impl LayeredAssetIo {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self { roots }
+    }
+
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        self.roots
+            .iter()
+            .rev()
+            .map(|root| root.join(path))
+            .find(|path| path.exists())
+    }
+}
+
+impl AssetIo for LayeredAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        Box::pin(async move {
+            let full_path = self
+                .resolve(path)
+                .ok_or_else(|| AssetIoError::NotFound(path.to_owned()))?;
+            Ok(std::fs::read(full_path)?)
+        })
+    }
+
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for root in &self.roots {
+            let read_dir = if let Ok(read_dir) = std::fs::read_dir(root.join(path)) {
+                read_dir
+            } else {
+                continue;
+            };
+            for entry in read_dir.filter_map(|entry| entry.ok()) {
+                let child = path.join(entry.file_name());
+                if !paths.contains(&child) {
+                    paths.push(child);
+                }
+            }
+        }
+        if paths.is_empty() && !self.is_directory(path) {
+            return Err(AssetIoError::NotFound(path.to_owned()));
+        }
+        Ok(Box::new(paths.into_iter()))
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| root.join(path).is_dir())
+    }
+
+    fn watch_path_for_changes(&self, _path: &Path) -> Result<(), AssetIoError> {
+        Ok(())
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        Ok(())
+    }
+}
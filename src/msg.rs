@@ -5,7 +5,7 @@ use std::sync::Mutex;
 #[derive(Clone, Copy, PartialEq, Eq, Debug, strum::AsRefStr)]
 pub enum MsgKind {
     Notice,
-    _Warn,
+    Warn,
 }
 
 static MSG_QUEUE: Lazy<Mutex<VecDeque<(MsgKind, String)>>> =
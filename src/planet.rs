@@ -1,13 +1,35 @@
 use crate::defs::*;
-use geom::{Array2d, Coords};
+use crate::mods::ModInfo;
+use geom::{Array2d, Coords, Direction, RectIter};
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
 
+pub type PlanetRng = rand_pcg::Pcg32;
+
+/// Minimum width and height of maps, to place the core
+pub const MIN_MAP_SIZE: u32 = 2;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Tile {
     pub biome: Biome,
     pub land_feature: LandFeature,
     pub structure: Structure,
     pub biomass: f32,
+    pub rainfall: f32,
+    /// Temperature [°C]
+    pub temp: f32,
+    pub ice: bool,
+    /// Sine of the solar elevation, 0 at night
+    pub sunlight: f32,
+    pub pollution: f32,
+    /// Ticks that the condition of a biome transition has continued
+    pub transition_ticks: u32,
+    /// Damage to the structure based on this tile
+    #[serde(default)]
+    pub damage: u32,
+    /// Remaining ticks to complete the structure based on this tile
+    #[serde(default)]
+    pub build_ticks: u32,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -16,13 +38,83 @@ pub struct Player {
     pub material: f32,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Atmosphere {
+    /// CO2 concentration [ppm]
+    pub co2: f32,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self { co2: 400.0 }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Orbit {
+    /// Axial tilt [deg]
+    pub axial_tilt: f32,
+    /// Orbital period [ticks]
+    pub year_length: u64,
+    /// Rotation period [ticks]
+    pub day_length: u64,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Self {
+            axial_tilt: 23.4,
+            year_length: 960,
+            day_length: 8,
+        }
+    }
+}
+
+/// In-game date. A year starts from the vernal equinox.
+#[derive(Clone, Copy, Debug)]
+pub struct Date {
+    pub year: u64,
+    pub season: Season,
+    pub day: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DisasterRecord {
+    pub tick: u64,
+    pub kind: DisasterKind,
+    pub pos: Coords,
+}
+
+impl Tile {
+    /// The value of an overlay normalized to 0.0 - 1.0
+    pub fn overlay_value(&self, overlay: Overlay) -> Option<f32> {
+        let (value, min, max) = match overlay {
+            Overlay::None => return None,
+            Overlay::Biomass => (self.biomass, 0.0, 3.0),
+            Overlay::Rainfall => (self.rainfall, 0.0, 1500.0),
+            Overlay::Temperature => (self.temp, -30.0, 40.0),
+            Overlay::Sunlight => (self.sunlight, 0.0, 1.0),
+            Overlay::Pollution => (self.pollution, 0.0, 2.0),
+        };
+        Some(((value - min) / (max - min)).clamp(0.0, 1.0))
+    }
+}
+
 impl Default for Tile {
     fn default() -> Self {
         Self {
-            biome: Biome::Ocean,
+            biome: Biome::default(),
             land_feature: LandFeature::None,
             structure: Structure::None,
             biomass: 0.0,
+            rainfall: 0.0,
+            temp: 0.0,
+            ice: false,
+            sunlight: 0.0,
+            pollution: 0.0,
+            transition_ticks: 0,
+            damage: 0,
+            build_ticks: 0,
         }
     }
 }
@@ -30,18 +122,33 @@ impl Default for Tile {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Planet {
     pub tick: u64,
+    /// Biome ids indexed by `Biome`, to keep tiles valid when the biome list changes
+    pub biome_ids: Vec<String>,
+    /// Mods active when the planet was saved
+    #[serde(default)]
+    pub mods: Vec<ModInfo>,
     pub player: Player,
     pub map: Array2d<Tile>,
+    pub atmo: Atmosphere,
+    pub orbit: Orbit,
+    pub disasters: Vec<DisasterRecord>,
+    pub rng: PlanetRng,
 }
 
 impl Planet {
-    pub fn new(w: u32, h: u32) -> Planet {
+    pub fn new(w: u32, h: u32, biome_ids: Vec<String>) -> Planet {
         let map = Array2d::new(w, h, Tile::default());
 
         let mut planet = Planet {
             tick: 0,
+            biome_ids,
+            mods: crate::mods::active_infos(),
             player: Player::default(),
             map,
+            atmo: Atmosphere::default(),
+            orbit: Orbit::default(),
+            disasters: Vec::new(),
+            rng: PlanetRng::from_entropy(),
         };
 
         planet.place(
@@ -54,11 +161,7 @@ impl Planet {
     }
 
     pub fn placeable(&self, p: Coords, size: StructureSize) -> bool {
-        if !self.map.in_range(p) {
-            return false;
-        }
-
-        for p in size.occupied_tiles().into_iter() {
+        for p in footprint(p, size) {
             if let Some(tile) = self.map.get(p) {
                 if !matches!(tile.structure, Structure::None) {
                     return false;
@@ -79,4 +182,179 @@ impl Planet {
             self.map[p + p_rel].structure = Structure::Occupied { by: p };
         }
     }
+
+    /// Whether the structure can be built at `p` by its placement rules and cost
+    pub fn buildable(&self, p: Coords, attrs: &StructureAttrs) -> bool {
+        if !self.placeable(p, attrs.size) {
+            return false;
+        }
+
+        let placement = &attrs.placement;
+        let tiles = footprint(p, attrs.size);
+        if !placement.biomes.is_empty()
+            && !tiles.iter().all(|p| {
+                placement
+                    .biomes
+                    .iter()
+                    .any(|id| id == self.biome_id(self.map[*p].biome))
+            })
+        {
+            return false;
+        }
+        if let Some(land_feature) = &placement.land_feature {
+            if self.map[p].land_feature != *land_feature {
+                return false;
+            }
+        }
+        if placement.connected {
+            let connected = tiles.iter().any(|p| {
+                Direction::EIGHT_DIRS.into_iter().any(|d| {
+                    let p_adj = *p + d.as_coords();
+                    !tiles.contains(&p_adj)
+                        && matches!(self.map.get(p_adj), Some(tile) if !matches!(tile.structure, Structure::None))
+                })
+            });
+            if !connected {
+                return false;
+            }
+        }
+
+        self.player.energy >= attrs.cost.energy && self.player.material >= attrs.cost.material
+    }
+
+    /// Pay the cost and start building the structure
+    pub fn build(&mut self, p: Coords, structure: Structure, attrs: &StructureAttrs) {
+        assert!(self.buildable(p, attrs));
+
+        self.player.energy -= attrs.cost.energy;
+        self.player.material -= attrs.cost.material;
+        self.place(p, attrs.size, structure);
+        self.map[p].damage = 0;
+        self.map[p].build_ticks = attrs.build_time;
+    }
+
+    /// Id of the biome, or an empty string if it is not in the biome table
+    pub fn biome_id(&self, biome: Biome) -> &str {
+        self.biome_ids
+            .get(biome.index())
+            .map(|id| id.as_str())
+            .unwrap_or("")
+    }
+
+    pub fn find_biome(&self, id: &str) -> Option<Biome> {
+        self.biome_ids
+            .iter()
+            .position(|biome_id| biome_id == id)
+            .map(|i| Biome(i as u16))
+    }
+
+    /// Renumber the biomes of tiles to follow the given biome list.
+    /// Tiles whose biome is not in the list become the first biome,
+    /// and the ids of such biomes are returned with the number of tiles.
+    pub fn rebase_biomes(&mut self, ids: &[String]) -> Vec<(String, usize)> {
+        if self.biome_ids == ids {
+            return Vec::new();
+        }
+
+        let mapping: Vec<Option<Biome>> = self
+            .biome_ids
+            .iter()
+            .map(|id| {
+                ids.iter()
+                    .position(|new_id| new_id == id)
+                    .map(|i| Biome(i as u16))
+            })
+            .collect();
+        let mut missing: Vec<(String, usize)> = Vec::new();
+
+        let (w, h) = self.map.size();
+        for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+            let tile = &mut self.map[p];
+            if let Some(biome) = mapping.get(tile.biome.index()).copied().flatten() {
+                tile.biome = biome;
+                continue;
+            }
+            let id = self
+                .biome_ids
+                .get(tile.biome.index())
+                .cloned()
+                .unwrap_or_else(|| tile.biome.0.to_string());
+            if let Some(entry) = missing.iter_mut().find(|(missing_id, _)| *missing_id == id) {
+                entry.1 += 1;
+            } else {
+                missing.push((id, 1));
+            }
+            tile.biome = Biome::default();
+            tile.transition_ticks = 0;
+        }
+
+        self.biome_ids = ids.to_vec();
+        missing
+    }
+
+    pub fn date(&self) -> Date {
+        let day_length = self.orbit.day_length.max(1);
+        let days_in_year = (self.orbit.year_length / day_length).max(4);
+        let season_length = days_in_year / 4;
+        let days = self.tick / day_length;
+        let day_of_year = days % days_in_year;
+        let season = match (day_of_year / season_length).min(3) {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        };
+
+        Date {
+            year: days / days_in_year + 1,
+            season,
+            day: day_of_year - (day_of_year / season_length).min(3) * season_length + 1,
+        }
+    }
+
+    /// Latitude [deg] of the tile row `y`
+    pub fn latitude(&self, y: i32) -> f32 {
+        ((y as f32 + 0.5) / self.map.size().1 as f32 - 0.5) * 180.0
+    }
+
+    /// Latitude [deg] where the sun is at the zenith at noon
+    pub fn solar_declination(&self) -> f32 {
+        let year_length = self.orbit.year_length.max(1);
+        let phase = (self.tick % year_length) as f32 / year_length as f32;
+        self.orbit.axial_tilt * (phase * std::f32::consts::TAU).sin()
+    }
+
+    /// Hour angle [rad] of the sun at the tile column `x`, 0 at noon
+    pub fn hour_angle(&self, x: i32) -> f32 {
+        let day_length = self.orbit.day_length.max(1);
+        let time_of_day = (self.tick % day_length) as f32 / day_length as f32;
+        let longitude = (x as f32 + 0.5) / self.map.size().0 as f32;
+        ((time_of_day + longitude) % 1.0 - 0.5) * std::f32::consts::TAU
+    }
+
+    /// Remove the structure on `p` and the tiles occupied by it
+    pub fn remove_structure(&mut self, p: Coords) {
+        let p = if let Structure::Occupied { by } = self.map[p].structure {
+            by
+        } else {
+            p
+        };
+
+        let (w, h) = self.map.size();
+        for p_occupied in RectIter::new((0, 0), (w - 1, h - 1)) {
+            if matches!(self.map[p_occupied].structure, Structure::Occupied { by } if by == p) {
+                self.map[p_occupied].structure = Structure::None;
+            }
+        }
+        self.map[p].structure = Structure::None;
+        self.map[p].damage = 0;
+        self.map[p].build_ticks = 0;
+    }
+}
+
+/// Tiles occupied by a structure based on `p`
+pub fn footprint(p: Coords, size: StructureSize) -> Vec<Coords> {
+    std::iter::once(p)
+        .chain(size.occupied_tiles().into_iter().map(|p_rel| p + p_rel))
+        .collect()
 }
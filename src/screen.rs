@@ -1,6 +1,6 @@
 use crate::action::CursorAction;
 use crate::assets::AssetsLoaded;
-use crate::defs::{Biome, StructureKind, StructureSize, TILE_SIZE};
+use crate::defs::{Biome, Overlay, StructureKind, StructureSize, TILE_SIZE};
 use crate::planet::Planet;
 use bevy::math::{Rect, Vec3Swizzles};
 use bevy::prelude::*;
@@ -33,6 +33,7 @@ impl Plugin for ScreenPlugin {
             .init_resource::<InScreenTileRange>()
             .init_resource::<HoverTile>()
             .init_resource::<CursorMode>()
+            .init_resource::<Overlay>()
             .add_system(centering.before("draw"))
             .add_system(
                 update_hover_tile
@@ -253,7 +254,11 @@ fn update_hover_tile(
     };
 
     let size = match &*cursor_mode {
-        CursorMode::Build(kind) => assets.structures[kind].attrs.size,
+        CursorMode::Build(kind) => assets
+            .structures
+            .get(kind)
+            .map(|asset| asset.attrs.size)
+            .unwrap_or_default(),
         CursorMode::EditBiome(_) => StructureSize::Small,
         _ => {
             return;
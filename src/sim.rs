@@ -1,79 +1,245 @@
-use anyhow::Result;
+mod autosave;
+mod biome;
+mod climate;
+mod disaster;
+pub mod history;
+mod pollution;
+mod production;
+pub mod save;
+pub mod validate;
+
 use bevy::core::FixedTimestep;
 use bevy::prelude::*;
+use std::path::PathBuf;
 
-use crate::defs::TILE_SIZE;
+use crate::assets::AssetsLoaded;
+use crate::defs::{Overlay, TILE_SIZE};
+use crate::msg::{push_msg, MsgKind};
 use crate::planet::Planet;
 use crate::screen::Centering;
+use history::History;
+
+#[derive(Clone, Debug)]
+pub struct SimPlugin {
+    pub save_conf: SaveConf,
+}
 
-#[derive(Clone, Copy, Debug)]
-pub struct SimPlugin;
+#[derive(Clone, Debug)]
+pub struct SaveConf {
+    pub dir: PathBuf,
+    /// Autosave interval in ticks, 0 to disable
+    pub autosave_ticks: u64,
+    /// Autosave interval in real minutes, 0 to disable
+    pub autosave_minutes: f64,
+    /// Number of rotating autosave files
+    pub autosave_slots: usize,
+    /// Repair broken planets on loading instead of rejecting them
+    pub repair: bool,
+}
 
 #[derive(Clone, Debug)]
 pub enum ManagePlanet {
     New(u32, u32),
     Save(String),
     Load(String),
+    ImportPng {
+        biome: String,
+        land_feature: Option<String>,
+    },
+    ExportPng(String, Overlay),
+    /// Restore the snapshot at the tick
+    Rewind(u64),
 }
 
 impl Plugin for SimPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ManagePlanet>()
-            .add_system(manage_planet.before("draw"))
+            .insert_resource(self.save_conf.clone())
+            .init_resource::<History>()
+            .add_system(manage_planet.after("ui_windows").before("draw"))
+            .add_system(rebase_biomes.after(manage_planet).before("draw"))
+            .add_system(autosave::autosave)
             .add_system_set(
                 SystemSet::new()
                     .with_run_criteria(FixedTimestep::step(2.0))
-                    .with_system(update),
+                    .with_system(update.after(rebase_biomes)),
             );
     }
 }
 
-fn update(mut planet: ResMut<Planet>) {
+fn update(
+    mut planet: ResMut<Planet>,
+    assets: Option<Res<AssetsLoaded>>,
+    mut history: ResMut<History>,
+) {
+    let assets = if let Some(assets) = &assets {
+        assets
+    } else {
+        return;
+    };
+    if history.rewinding {
+        return;
+    }
+
     planet.tick += 1;
+    climate::advance(&mut planet, &assets.biomes);
+    production::produce(&mut planet, &assets.structures);
+    pollution::advance(&mut planet);
+    biome::succession(&mut planet, &assets.biomes);
+    disaster::cause_disasters(&mut planet, &assets.biomes, &assets.structures);
+
+    if history.due(planet.tick) {
+        if let Err(e) = history.record(&planet) {
+            log::warn!("cannot record history: {:?}", e);
+        }
+    }
 }
 
 fn manage_planet(
     mut er_manage_planet: EventReader<ManagePlanet>,
     mut planet: ResMut<Planet>,
     mut ew_centering: EventWriter<Centering>,
+    assets: Option<Res<AssetsLoaded>>,
+    save_conf: Res<SaveConf>,
+    mut history: ResMut<History>,
+    mut deferred: Local<Vec<ManagePlanet>>,
 ) {
-    for e in er_manage_planet.iter() {
+    // Events needing assets are deferred until assets are loaded
+    let events: Vec<ManagePlanet> = std::mem::take(&mut *deferred)
+        .into_iter()
+        .chain(er_manage_planet.iter().cloned())
+        .collect();
+    for e in &events {
         match e {
             ManagePlanet::New(w, h) => {
-                *planet = Planet::new(*w, *h);
-                ew_centering.send(Centering(Vec2::new(
-                    *w as f32 * TILE_SIZE / 2.0,
-                    *h as f32 * TILE_SIZE / 2.0,
-                )));
+                let assets = if let Some(assets) = &assets {
+                    assets
+                } else {
+                    deferred.push(e.clone());
+                    continue;
+                };
+                *planet = Planet::new(*w, *h, assets.biome_ids());
+                history.clear();
+                ew_centering.send(centering(&planet));
             }
             ManagePlanet::Save(path) => {
-                if let Err(e) = save(&planet, path) {
+                if let Err(e) = save::save(&planet, path) {
                     log::warn!("cannot save: {:?}", e);
+                    push_msg(MsgKind::Warn, t!("save-failed"; error = format!("{:#}", e)));
+                }
+            }
+            ManagePlanet::Load(path) => {
+                let assets = if let Some(assets) = &assets {
+                    assets
+                } else {
+                    deferred.push(e.clone());
+                    continue;
+                };
+                match validate::load_checked(
+                    path,
+                    &assets.structure_sizes(),
+                    &assets.biome_ids(),
+                    save_conf.repair,
+                ) {
+                    Ok((mut new_planet, problems)) => {
+                        if !problems.is_empty() {
+                            let problems = validate::join(&problems);
+                            log::warn!("repaired planet: {}", problems);
+                            push_msg(MsgKind::Warn, t!("load-repaired"; problems = problems));
+                        }
+                        if let Some(differences) = crate::mods::adopt(&mut new_planet.mods) {
+                            log::warn!("mods differ from the saved planet: {}", differences);
+                            push_msg(MsgKind::Warn, t!("mods-differ"; mods = differences));
+                        }
+                        *planet = new_planet;
+                        history.clear();
+                        ew_centering.send(centering(&planet));
+                    }
+                    Err(e) => {
+                        log::warn!("cannot load: {:?}", e);
+                        push_msg(MsgKind::Warn, t!("load-failed"; error = format!("{:#}", e)));
+                    }
+                }
+            }
+            ManagePlanet::ImportPng {
+                biome,
+                land_feature,
+            } => {
+                let assets = if let Some(assets) = &assets {
+                    assets
+                } else {
+                    deferred.push(e.clone());
+                    continue;
+                };
+                match crate::map_image::import_png(
+                    biome,
+                    land_feature.as_ref(),
+                    &assets.biome_colors(),
+                ) {
+                    Ok(new_planet) => {
+                        *planet = new_planet;
+                        history.clear();
+                        ew_centering.send(centering(&planet));
+                    }
+                    Err(e) => {
+                        log::warn!("cannot import: {:?}", e);
+                        push_msg(MsgKind::Warn, t!("load-failed"; error = format!("{:#}", e)));
+                    }
                 }
             }
-            ManagePlanet::Load(path) => match load(path) {
+            ManagePlanet::Rewind(tick) => match history.restore(*tick) {
                 Ok(new_planet) => {
                     *planet = new_planet;
-                    ew_centering.send(Centering(Vec2::new(
-                        planet.map.size().0 as f32 * TILE_SIZE / 2.0,
-                        planet.map.size().1 as f32 * TILE_SIZE / 2.0,
-                    )));
                 }
                 Err(e) => {
-                    log::warn!("cannot load: {:?}", e);
+                    log::warn!("cannot rewind: {:?}", e);
                 }
             },
+            ManagePlanet::ExportPng(path, layer) => {
+                let assets = if let Some(assets) = &assets {
+                    assets
+                } else {
+                    deferred.push(e.clone());
+                    continue;
+                };
+                if let Err(e) =
+                    crate::map_image::export_png(&planet, path, *layer, &assets.biome_colors())
+                {
+                    log::warn!("cannot export: {:?}", e);
+                    push_msg(MsgKind::Warn, t!("save-failed"; error = format!("{:#}", e)));
+                }
+            }
         }
     }
 }
 
-fn save(planet: &Planet, path: &str) -> Result<()> {
-    let w = std::fs::File::create(path)?;
-    bincode::serialize_into(w, planet)?;
-    Ok(())
+/// Keep the biomes of the planet following the biome list,
+/// which changes by reloading assets or rewinding to a planet with an old list.
+/// Runs before `update`, whose biome indices must point into the current list
+fn rebase_biomes(mut planet: ResMut<Planet>, assets: Option<Res<AssetsLoaded>>) {
+    let assets = if let Some(assets) = &assets {
+        assets
+    } else {
+        return;
+    };
+    if planet.biome_ids.len() == assets.biomes.len()
+        && planet
+            .biome_ids
+            .iter()
+            .zip(&assets.biomes)
+            .all(|(id, asset)| *id == asset.attrs.id)
+    {
+        return;
+    }
+
+    for (id, count) in planet.rebase_biomes(&assets.biome_ids()) {
+        log::warn!("{} tile(s) of removed biome \"{}\" are reset", count, id);
+    }
 }
 
-fn load(path: &str) -> Result<Planet> {
-    let r = std::fs::File::open(path)?;
-    Ok(bincode::deserialize_from(r)?)
+fn centering(planet: &Planet) -> Centering {
+    Centering(Vec2::new(
+        planet.map.size().0 as f32 * TILE_SIZE / 2.0,
+        planet.map.size().1 as f32 * TILE_SIZE / 2.0,
+    ))
 }
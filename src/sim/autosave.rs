@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::{save, SaveConf};
+use crate::planet::Planet;
+
+#[derive(Default)]
+pub struct AutosaveState {
+    last_tick: u64,
+    last_time: f64,
+    writing: Arc<AtomicBool>,
+}
+
+pub fn autosave(
+    planet: Res<Planet>,
+    conf: Res<SaveConf>,
+    time: Res<Time>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    mut state: Local<AutosaveState>,
+) {
+    if conf.autosave_slots == 0 {
+        return;
+    }
+
+    // The tick goes back when another planet is loaded
+    if planet.tick < state.last_tick {
+        state.last_tick = planet.tick;
+    }
+
+    let now = time.seconds_since_startup();
+    let by_tick = conf.autosave_ticks > 0 && planet.tick >= state.last_tick + conf.autosave_ticks;
+    let by_time =
+        conf.autosave_minutes > 0.0 && now >= state.last_time + conf.autosave_minutes * 60.0;
+    if !(by_tick || by_time) || state.writing.load(Ordering::Acquire) {
+        return;
+    }
+    state.last_tick = planet.tick;
+    state.last_time = now;
+
+    // Only cloning is done in this frame, serializing and writing are done in the task
+    let planet = planet.clone();
+    let dir = conf.dir.clone();
+    let slots = conf.autosave_slots;
+    let writing = state.writing.clone();
+    writing.store(true, Ordering::Release);
+
+    task_pool
+        .spawn(async move {
+            let path = next_slot(&dir, slots);
+            let result = std::fs::create_dir_all(&dir)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| save::save(&planet, &path));
+            match result {
+                Ok(()) => log::info!("autosaved to {}", path.display()),
+                Err(e) => log::warn!("cannot autosave to {}: {:?}", path.display(), e),
+            }
+            writing.store(false, Ordering::Release);
+        })
+        .detach();
+}
+
+pub fn slot_path(dir: &Path, slot: usize) -> PathBuf {
+    dir.join(format!("autosave-{}.bin", slot))
+}
+
+/// An empty slot or the oldest one
+fn next_slot(dir: &Path, slots: usize) -> PathBuf {
+    (0..slots)
+        .map(|slot| slot_path(dir, slot))
+        .min_by_key(|path| {
+            std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .expect("no autosave slot")
+}
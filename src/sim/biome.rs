@@ -0,0 +1,56 @@
+use geom::RectIter;
+
+use crate::assets::BiomeAsset;
+use crate::defs::Biome;
+use crate::planet::Planet;
+
+/// Ticks that a tile stays out of the climate envelope of its biome before changing
+const ENVELOPE_DURATION: u32 = 50;
+
+/// Change biomes whose transition conditions have continued long enough.
+/// Tiles out of the climate envelope of their biome change to the first biome
+/// of the same water state whose envelope fits.
+pub fn succession(planet: &mut Planet, biomes: &[BiomeAsset]) {
+    let (w, h) = planet.map.size();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        let attrs = if let Some(asset) = biomes.get(tile.biome.index()) {
+            &asset.attrs
+        } else {
+            continue;
+        };
+        let transition = attrs
+            .transitions
+            .iter()
+            .find(|transition| transition.satisfied(tile.rainfall, tile.biomass))
+            .map(|transition| {
+                (
+                    biomes
+                        .iter()
+                        .position(|asset| asset.attrs.id == transition.to),
+                    transition.duration,
+                )
+            });
+        let transition = transition.or_else(|| {
+            if attrs.climate.contains(tile.temp, tile.rainfall) {
+                return None;
+            }
+            let next = biomes.iter().position(|asset| {
+                asset.attrs.water == attrs.water
+                    && asset.attrs.climate.contains(tile.temp, tile.rainfall)
+            });
+            Some((next, ENVELOPE_DURATION))
+        });
+
+        if let Some((Some(next), duration)) = transition {
+            tile.transition_ticks += 1;
+            if tile.transition_ticks >= duration {
+                tile.biome = Biome(next as u16);
+                tile.transition_ticks = 0;
+            }
+        } else {
+            tile.transition_ticks = 0;
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use geom::{Array2d, Direction, RectIter};
+
+use super::pollution;
+use crate::assets::BiomeAsset;
+use crate::planet::Planet;
+
+/// Temperature [°C] without sunlight
+const BASE_TEMP: f32 = -30.0;
+/// Temperature [°C] raised by the sun at the zenith
+const INSOLATION_TEMP: f32 = 60.0;
+/// CO2 [ppm] which the base temperature assumes
+const BASE_CO2: f32 = 400.0;
+/// Temperature [°C] raised by doubled CO2
+const CO2_DOUBLING_TEMP: f32 = 3.0;
+/// Ratio of the gap to the equilibrium temperature that changes per tick
+const LAND_TEMP_RESPONSE: f32 = 0.3;
+const WATER_TEMP_RESPONSE: f32 = 0.1;
+/// Tiles freeze under this temperature [°C]
+const ICE_TEMP: f32 = -5.0;
+/// Ratio of humidity that frozen water supplies
+const ICE_HUMIDITY: f32 = 0.3;
+/// Plants start growing at the min temperature [°C] and fully grow at the max
+const GROWTH_MIN_TEMP: f32 = 0.0;
+const GROWTH_MAX_TEMP: f32 = 15.0;
+/// Rainfall [mm/year] when the air is saturated
+const MAX_RAINFALL: f32 = 1500.0;
+/// Biomass [kg/m^2] that 1 mm/year rainfall can support
+const BIOMASS_PER_RAINFALL: f32 = 0.002;
+/// Ratio of the gap to the capacity that biomass grows per tick
+const BIOMASS_GROWTH_RATE: f32 = 0.1;
+
+pub fn advance(planet: &mut Planet, biomes: &[BiomeAsset]) {
+    update_sunlight(planet);
+    update_temp(planet, biomes);
+    update_rainfall(planet, biomes);
+    update_biomass(planet, biomes);
+}
+
+/// The sun moves along the longitude with the planet rotation
+fn update_sunlight(planet: &mut Planet) {
+    let (w, h) = planet.map.size();
+    let declination = planet.solar_declination().to_radians();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let latitude = planet.latitude(p.1).to_radians();
+        let hour_angle = planet.hour_angle(p.0);
+        let sunlight = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+        planet.map[p].sunlight = sunlight.max(0.0);
+    }
+}
+
+/// Insolation by the latitude follows the seasons, and tiles approach to
+/// the temperature slowly, water more slowly than lands.
+fn update_temp(planet: &mut Planet, biomes: &[BiomeAsset]) {
+    let (w, h) = planet.map.size();
+    let declination = planet.solar_declination();
+    let greenhouse = CO2_DOUBLING_TEMP * (planet.atmo.co2 / BASE_CO2).max(f32::EPSILON).log2();
+
+    for y in 0..h as i32 {
+        let insolation = (planet.latitude(y) - declination)
+            .to_radians()
+            .cos()
+            .max(0.0);
+        let equilibrium = BASE_TEMP + INSOLATION_TEMP * insolation + greenhouse;
+
+        for x in 0..w as i32 {
+            let tile = &mut planet.map[(x, y)];
+            let (equilibrium, response) = match biomes.get(tile.biome.index()) {
+                Some(asset) if asset.attrs.water => {
+                    (equilibrium + asset.attrs.temp_offset, WATER_TEMP_RESPONSE)
+                }
+                Some(asset) => (equilibrium + asset.attrs.temp_offset, LAND_TEMP_RESPONSE),
+                None => (equilibrium, LAND_TEMP_RESPONSE),
+            };
+            tile.temp += (equilibrium - tile.temp) * response;
+            tile.ice = tile.temp < ICE_TEMP;
+        }
+    }
+}
+
+/// Humidity comes from water and spreads inland, so tiles far from water
+/// or behind biomes with low humidity transfer like mountains get less rainfall.
+fn update_rainfall(planet: &mut Planet, biomes: &[BiomeAsset]) {
+    let (w, h) = planet.map.size();
+    let mut rainfall = Array2d::new(w, h, 0.0f32);
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let attrs = biomes
+            .get(planet.map[p].biome.index())
+            .map(|asset| &asset.attrs);
+        if attrs.map(|attrs| attrs.water).unwrap_or(false) {
+            rainfall[p] = if planet.map[p].ice {
+                MAX_RAINFALL * ICE_HUMIDITY
+            } else {
+                MAX_RAINFALL
+            };
+            continue;
+        }
+
+        let mut sum = 0.0;
+        let mut n = 0;
+        for d in Direction::EIGHT_DIRS {
+            let p_adj = p + d.as_coords();
+            if let Some(tile) = planet.map.get(p_adj) {
+                sum += tile.rainfall;
+                n += 1;
+            }
+        }
+        let transfer = attrs.map(|attrs| attrs.humidity_transfer).unwrap_or(0.0);
+        rainfall[p] = if n > 0 {
+            sum / n as f32 * transfer
+        } else {
+            0.0
+        };
+    }
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        planet.map[p].rainfall = rainfall[p];
+    }
+}
+
+fn update_biomass(planet: &mut Planet, biomes: &[BiomeAsset]) {
+    let (w, h) = planet.map.size();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        let attrs = if let Some(asset) = biomes.get(tile.biome.index()) {
+            &asset.attrs
+        } else {
+            continue;
+        };
+        if attrs.water {
+            continue;
+        }
+        let capacity = tile.rainfall * BIOMASS_PER_RAINFALL * attrs.fertility;
+        let season_factor =
+            ((tile.temp - GROWTH_MIN_TEMP) / (GROWTH_MAX_TEMP - GROWTH_MIN_TEMP)).clamp(0.0, 1.0);
+        let growth_rate = BIOMASS_GROWTH_RATE
+            * season_factor
+            * tile.sunlight
+            * pollution::growth_factor(tile.pollution);
+        tile.biomass += (capacity - tile.biomass) * growth_rate;
+    }
+}
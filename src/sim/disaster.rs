@@ -0,0 +1,201 @@
+use fnv::FnvHashMap;
+use geom::{Array2d, Coords, Direction, RectIter};
+use rand::Rng;
+use std::collections::VecDeque;
+
+use crate::assets::{BiomeAsset, StructureAsset};
+use crate::defs::{DisasterKind, Structure, StructureKind};
+use crate::msg::{push_msg, MsgKind};
+use crate::planet::{DisasterRecord, Planet};
+
+/// Probability of a meteor strike per tick
+const METEOR_PROB: f64 = 0.002;
+/// Probability of a volcanic eruption per tick
+const VOLCANO_PROB: f64 = 0.002;
+/// Probability of a wildfire per tick
+const WILDFIRE_PROB: f64 = 0.01;
+
+const METEOR_RADIUS: i32 = 1;
+const VOLCANO_RADIUS: i32 = 1;
+/// Damage to structures in the area
+const METEOR_DAMAGE: u32 = 80;
+const VOLCANO_DAMAGE: u32 = 150;
+/// CO2 [ppm] emitted by an eruption
+const VOLCANO_CO2: f32 = 5.0;
+/// Biome ids that disasters leave on land. The biome is kept if not in the list.
+const METEOR_BIOME: &str = "desert";
+const VOLCANO_BIOME: &str = "mountains";
+/// Minimum biomass [kg/m^2] that fire can spread through
+const WILDFIRE_MIN_BIOMASS: f32 = 1.5;
+const WILDFIRE_MAX_TILES: usize = 64;
+/// CO2 [ppm] emitted by burning 1 kg/m^2 biomass on a tile
+const WILDFIRE_CO2_PER_BIOMASS: f32 = 0.01;
+
+pub fn cause_disasters(
+    planet: &mut Planet,
+    biomes: &[BiomeAsset],
+    structures: &FnvHashMap<StructureKind, StructureAsset>,
+) {
+    if planet.rng.gen_bool(METEOR_PROB) {
+        let p = random_pos(planet);
+        meteor(planet, p, biomes, structures);
+        record(planet, DisasterKind::Meteor, p);
+    }
+
+    if planet.rng.gen_bool(VOLCANO_PROB) {
+        let p = random_pos(planet);
+        volcano(planet, p, structures);
+        record(planet, DisasterKind::Volcano, p);
+    }
+
+    if planet.rng.gen_bool(WILDFIRE_PROB) {
+        let p = random_pos(planet);
+        if flammable(planet, p, biomes) {
+            wildfire(planet, p, biomes);
+            record(planet, DisasterKind::Wildfire, p);
+        }
+    }
+}
+
+fn random_pos(planet: &mut Planet) -> Coords {
+    let (w, h) = planet.map.size();
+    Coords(
+        planet.rng.gen_range(0..w as i32),
+        planet.rng.gen_range(0..h as i32),
+    )
+}
+
+fn record(planet: &mut Planet, kind: DisasterKind, pos: Coords) {
+    planet.disasters.push(DisasterRecord {
+        tick: planet.tick,
+        kind,
+        pos,
+    });
+    push_msg(
+        MsgKind::Warn,
+        t!(&format!("disaster-{}", kind.as_ref()); x = pos.0, y = pos.1),
+    );
+}
+
+/// Tiles in the area excluding the core, which must survive disasters
+fn affected_tiles(planet: &Planet, center: Coords, r: i32) -> Vec<Coords> {
+    RectIter::new(center + (-r, -r), center + (r, r))
+        .filter(|p| planet.map.in_range(*p))
+        .filter(|p| {
+            let structure = match planet.map[*p].structure {
+                Structure::Occupied { by } => &planet.map[by].structure,
+                ref structure => structure,
+            };
+            !matches!(structure, Structure::Core)
+        })
+        .collect()
+}
+
+fn meteor(
+    planet: &mut Planet,
+    center: Coords,
+    biomes: &[BiomeAsset],
+    structures: &FnvHashMap<StructureKind, StructureAsset>,
+) {
+    let tiles = affected_tiles(planet, center, METEOR_RADIUS);
+    damage_structures(planet, &tiles, METEOR_DAMAGE, structures);
+    let desert = planet.find_biome(METEOR_BIOME);
+    for p in tiles {
+        let tile = &mut planet.map[p];
+        tile.biomass = 0.0;
+        let water = biomes
+            .get(tile.biome.index())
+            .map(|asset| asset.attrs.water)
+            .unwrap_or(false);
+        if let Some(desert) = desert.filter(|_| !water) {
+            tile.biome = desert;
+        }
+    }
+}
+
+fn volcano(
+    planet: &mut Planet,
+    center: Coords,
+    structures: &FnvHashMap<StructureKind, StructureAsset>,
+) {
+    let tiles = affected_tiles(planet, center, VOLCANO_RADIUS);
+    damage_structures(planet, &tiles, VOLCANO_DAMAGE, structures);
+    let mountains = planet.find_biome(VOLCANO_BIOME);
+    for p in tiles {
+        let tile = &mut planet.map[p];
+        tile.biomass = 0.0;
+        if let Some(mountains) = mountains {
+            tile.biome = mountains;
+        }
+    }
+    planet.atmo.co2 += VOLCANO_CO2;
+}
+
+/// Damage structures on the tiles, and remove ones whose hit points run out
+fn damage_structures(
+    planet: &mut Planet,
+    tiles: &[Coords],
+    damage: u32,
+    structures: &FnvHashMap<StructureKind, StructureAsset>,
+) {
+    let mut bases: Vec<Coords> = Vec::new();
+    for p in tiles {
+        let base = match planet.map[*p].structure {
+            Structure::None => continue,
+            Structure::Occupied { by } => by,
+            _ => *p,
+        };
+        if !bases.contains(&base) {
+            bases.push(base);
+        }
+    }
+
+    for p in bases {
+        let hp = structures
+            .get(&StructureKind::from(&planet.map[p].structure))
+            .map(|asset| asset.attrs.hp)
+            .unwrap_or(0);
+        let tile = &mut planet.map[p];
+        tile.damage = tile.damage.saturating_add(damage);
+        if tile.damage >= hp {
+            planet.remove_structure(p);
+        }
+    }
+}
+
+fn flammable(planet: &Planet, p: Coords, biomes: &[BiomeAsset]) -> bool {
+    let tile = &planet.map[p];
+    tile.biomass >= WILDFIRE_MIN_BIOMASS
+        && biomes
+            .get(tile.biome.index())
+            .map(|asset| asset.attrs.flammable)
+            .unwrap_or(false)
+}
+
+/// Fire spreads through flammable tiles, so biomes like oceans and deserts stop it
+fn wildfire(planet: &mut Planet, start: Coords, biomes: &[BiomeAsset]) {
+    let (w, h) = planet.map.size();
+    let mut visited = Array2d::new(w, h, false);
+    let mut queue = VecDeque::new();
+    let mut n_burned = 0;
+    visited[start] = true;
+    queue.push_back(start);
+
+    while let Some(p) = queue.pop_front() {
+        let tile = &mut planet.map[p];
+        planet.atmo.co2 += tile.biomass * WILDFIRE_CO2_PER_BIOMASS;
+        tile.biomass = 0.0;
+        n_burned += 1;
+        if n_burned >= WILDFIRE_MAX_TILES {
+            break;
+        }
+
+        for d in Direction::EIGHT_DIRS {
+            let p_adj = p + d.as_coords();
+            if planet.map.in_range(p_adj) && !visited[p_adj] && flammable(planet, p_adj, biomes) {
+                visited[p_adj] = true;
+                queue.push_back(p_adj);
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::{bail, Result};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+use crate::planet::Planet;
+
+/// Ticks between snapshots
+const SNAPSHOT_INTERVAL: u64 = 10;
+/// Max number of kept snapshots
+const MAX_SNAPSHOTS: usize = 300;
+/// Every this number of snapshots is stored fully, others are stored as deltas
+const KEYFRAME_INTERVAL: usize = 20;
+
+/// In-memory snapshots of the planet to rewind
+#[derive(Default)]
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    /// Serialized planet of the last snapshot
+    last: Vec<u8>,
+    /// The simulation stops while rewinding, to keep snapshots after the current tick
+    pub rewinding: bool,
+}
+
+struct Snapshot {
+    tick: u64,
+    /// Whether `data` is the whole planet or XOR with the previous snapshot
+    full: bool,
+    /// Deflate compressed data
+    data: Vec<u8>,
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.last.clear();
+    }
+
+    pub fn ticks(&self) -> impl Iterator<Item = u64> + '_ {
+        self.snapshots.iter().map(|snapshot| snapshot.tick)
+    }
+
+    /// Whether a snapshot should be recorded at the tick
+    pub fn due(&self, tick: u64) -> bool {
+        self.snapshots
+            .back()
+            .map(|snapshot| tick >= snapshot.tick + SNAPSHOT_INTERVAL || tick < snapshot.tick)
+            .unwrap_or(true)
+    }
+
+    /// Add a snapshot. Snapshots not before the planet are discarded, to fork the history.
+    pub fn record(&mut self, planet: &Planet) -> Result<()> {
+        if self
+            .snapshots
+            .back()
+            .map(|snapshot| snapshot.tick >= planet.tick)
+            .unwrap_or(false)
+        {
+            while let Some(snapshot) = self.snapshots.back() {
+                if snapshot.tick < planet.tick {
+                    break;
+                }
+                self.snapshots.pop_back();
+            }
+            self.last = if self.snapshots.is_empty() {
+                Vec::new()
+            } else {
+                self.bytes(self.snapshots.len() - 1)?
+            };
+        }
+
+        let bytes = bincode::serialize(planet)?;
+        let since_keyframe = self
+            .snapshots
+            .iter()
+            .rev()
+            .take_while(|snapshot| !snapshot.full)
+            .count();
+        let full = self.snapshots.is_empty() || since_keyframe + 1 >= KEYFRAME_INTERVAL;
+        let data = if full {
+            compress(&bytes)?
+        } else {
+            compress(&xor(&bytes, &self.last))?
+        };
+        self.snapshots.push_back(Snapshot {
+            tick: planet.tick,
+            full,
+            data,
+        });
+        self.last = bytes;
+
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            // The next snapshot becomes a keyframe if it depends on the removed one
+            if !self.snapshots[1].full {
+                let bytes = self.bytes(1)?;
+                let next = &mut self.snapshots[1];
+                next.full = true;
+                next.data = compress(&bytes)?;
+            }
+            self.snapshots.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Restore the planet of the snapshot at the tick
+    pub fn restore(&self, tick: u64) -> Result<Planet> {
+        let i = if let Some(i) = self.ticks().position(|t| t == tick) {
+            i
+        } else {
+            bail!("no snapshot at tick {}", tick);
+        };
+        Ok(bincode::deserialize(&self.bytes(i)?)?)
+    }
+
+    /// Serialized planet of the i-th snapshot
+    fn bytes(&self, i: usize) -> Result<Vec<u8>> {
+        let keyframe = (0..=i)
+            .rev()
+            .find(|j| self.snapshots[*j].full)
+            .expect("the first snapshot is always full");
+
+        let mut bytes = decompress(&self.snapshots[keyframe].data)?;
+        for snapshot in self.snapshots.range((keyframe + 1)..=i) {
+            bytes = xor(&decompress(&snapshot.data)?, &bytes);
+        }
+        Ok(bytes)
+    }
+}
+
+/// XOR of `a` and `b` with the length of `a`. `b` is padded with zeros.
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter()
+        .enumerate()
+        .map(|(i, x)| x ^ b.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
+    e.write_all(data)?;
+    Ok(e.finish()?)
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
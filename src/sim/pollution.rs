@@ -0,0 +1,62 @@
+use geom::{Array2d, Direction, RectIter};
+
+use crate::planet::Planet;
+
+/// Pollution above this level suppresses biomass growth and harms living things
+pub const POLLUTION_THRESHOLD: f32 = 1.0;
+/// Ratio of pollution exchanged with adjacent tiles per tick
+const DIFFUSION_RATE: f32 = 0.2;
+/// Ratio of pollution decomposed per tick
+const DECAY_RATE: f32 = 0.01;
+/// Biomass [kg/m^2] lost per tick by pollution exceeding the threshold by 1.0
+const BIOMASS_DAMAGE: f32 = 0.05;
+
+pub fn advance(planet: &mut Planet) {
+    diffuse(planet);
+    damage(planet);
+}
+
+fn diffuse(planet: &mut Planet) {
+    let (w, h) = planet.map.size();
+    let mut pollution = Array2d::new(w, h, 0.0f32);
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let mut sum = 0.0;
+        let mut n = 0;
+        for d in Direction::EIGHT_DIRS {
+            if let Some(tile) = planet.map.get(p + d.as_coords()) {
+                sum += tile.pollution;
+                n += 1;
+            }
+        }
+        let current = planet.map[p].pollution;
+        let diffused = if n > 0 {
+            current * (1.0 - DIFFUSION_RATE) + sum / n as f32 * DIFFUSION_RATE
+        } else {
+            current
+        };
+        pollution[p] = diffused * (1.0 - DECAY_RATE);
+    }
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        planet.map[p].pollution = pollution[p];
+    }
+}
+
+fn damage(planet: &mut Planet) {
+    let (w, h) = planet.map.size();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        let excess = tile.pollution - POLLUTION_THRESHOLD;
+        if excess > 0.0 {
+            tile.biomass = (tile.biomass - excess * BIOMASS_DAMAGE).max(0.0);
+        }
+    }
+}
+
+/// Ratio of biomass growth under the pollution.
+/// Growth is not suppressed up to the threshold, and stops at twice the threshold.
+pub fn growth_factor(pollution: f32) -> f32 {
+    (1.0 - (pollution - POLLUTION_THRESHOLD) / POLLUTION_THRESHOLD).clamp(0.0, 1.0)
+}
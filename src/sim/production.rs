@@ -0,0 +1,58 @@
+use fnv::FnvHashMap;
+use geom::RectIter;
+
+use crate::assets::StructureAsset;
+use crate::defs::{Resources, Structure, StructureKind};
+use crate::planet::{Planet, Player};
+
+/// Build structures and produce resources by the structure attributes
+pub fn produce(planet: &mut Planet, structures: &FnvHashMap<StructureKind, StructureAsset>) {
+    let (w, h) = planet.map.size();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        if matches!(tile.structure, Structure::None | Structure::Occupied { .. }) {
+            continue;
+        }
+        let attrs = if let Some(asset) = structures.get(&StructureKind::from(&tile.structure)) {
+            &asset.attrs
+        } else {
+            continue;
+        };
+
+        if tile.build_ticks > 0 {
+            tile.build_ticks -= 1;
+            continue;
+        }
+
+        if !pay(&mut planet.player, &attrs.upkeep) {
+            continue;
+        }
+
+        let production = &attrs.production;
+        if !pay(&mut planet.player, &production.consumes) {
+            continue;
+        }
+        let sunlight = if production.by_sunlight {
+            tile.sunlight
+        } else {
+            1.0
+        };
+        planet.player.energy += production.produces.energy * sunlight;
+        planet.player.material += production.produces.material;
+        tile.pollution += production.pollution;
+    }
+}
+
+/// Pay the resources if the player has enough
+fn pay(player: &mut Player, resources: &Resources) -> bool {
+    if resources.is_zero() {
+        return true;
+    }
+    if player.energy < resources.energy || player.material < resources.material {
+        return false;
+    }
+    player.energy -= resources.energy;
+    player.material -= resources.material;
+    true
+}
@@ -0,0 +1,513 @@
+use anyhow::{bail, Context, Result};
+use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::planet::Planet;
+
+/// Magic number at the head of save files
+const MAGIC: [u8; 4] = *b"GAIA";
+/// Magic number of gzip streams, to distinguish from uncompressed saves
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Increment when the layout of `Planet` or `SaveHeader` changes,
+/// and add a migration from the previous version
+pub const FORMAT_VERSION: u32 = 4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaveHeader {
+    pub game_version: String,
+    pub map_size: (u32, u32),
+    pub tick: u64,
+}
+
+impl SaveHeader {
+    fn new(planet: &Planet) -> Self {
+        Self {
+            game_version: env!("CARGO_PKG_VERSION").into(),
+            map_size: planet.map.size(),
+            tick: planet.tick,
+        }
+    }
+}
+
+/// Planet data in each format version, upgraded one by one to the current version
+enum VersionedPlanet {
+    V0(v0::Planet),
+    V1(v1::Planet),
+    V2(v2::Planet),
+    V3(v3::Planet),
+    V4(Planet),
+}
+
+impl VersionedPlanet {
+    fn migrate(self) -> Planet {
+        match self {
+            VersionedPlanet::V0(planet) => VersionedPlanet::V1(planet.into()).migrate(),
+            VersionedPlanet::V1(planet) => VersionedPlanet::V2(planet.into()).migrate(),
+            VersionedPlanet::V2(planet) => VersionedPlanet::V3(planet.into()).migrate(),
+            VersionedPlanet::V3(planet) => VersionedPlanet::V4(planet.into()).migrate(),
+            VersionedPlanet::V4(planet) => planet,
+        }
+    }
+}
+
+/// Extensions of save files. Files with other extensions are saved in the binary format.
+const EXTENSIONS: &[&str] = &["bin", "ron", "json"];
+
+pub fn has_save_extension(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some(ext) if EXTENSIONS.contains(&ext))
+}
+
+/// File formats chosen by the extension. Text formats hold the plain planet
+/// for reading and editing by hand, and are migrated by trying older layouts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Bin,
+    Ron,
+    Json,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ron") => Format::Ron,
+            Some("json") => Format::Json,
+            _ => Format::Bin,
+        }
+    }
+}
+
+/// Save the planet atomically through a temporary file
+pub fn save<P: AsRef<Path>>(planet: &Planet, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+
+    let mut w = BufWriter::new(std::fs::File::create(&tmp_path)?);
+    match Format::from_path(path) {
+        Format::Bin => save_bin(planet, &mut w)?,
+        Format::Ron => {
+            ron::ser::to_writer_pretty(&mut w, planet, ron::ser::PrettyConfig::default())?
+        }
+        Format::Json => serde_json::to_writer_pretty(&mut w, planet)?,
+    }
+    w.into_inner()?.sync_all()?;
+
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Planet> {
+    let path = path.as_ref();
+    match Format::from_path(path) {
+        Format::Bin => load_bin(path),
+        Format::Ron | Format::Json => load_text(path, Format::from_path(path)),
+    }
+}
+
+/// Text saves have no version, so older layouts are tried if the current one fails
+fn load_text(path: &Path, format: Format) -> Result<Planet> {
+    let s = std::fs::read_to_string(path)?;
+    let e = match parse_text::<Planet>(&s, format) {
+        Ok(planet) => return Ok(planet),
+        Err(e) => e,
+    };
+
+    let planet = parse_text(&s, format)
+        .map(VersionedPlanet::V3)
+        .or_else(|_| parse_text(&s, format).map(VersionedPlanet::V2))
+        .or_else(|_| parse_text(&s, format).map(VersionedPlanet::V1));
+    match planet {
+        Ok(planet) => {
+            log::info!("migrating {} from an old layout", path.display());
+            Ok(planet.migrate())
+        }
+        // The error for the current layout is the most relevant for editing by hand
+        Err(_) => {
+            let name = if format == Format::Ron { "RON" } else { "JSON" };
+            Err(e).with_context(|| format!("invalid {} in {}", name, path.display()))
+        }
+    }
+}
+
+fn parse_text<T: serde::de::DeserializeOwned>(s: &str, format: Format) -> Result<T> {
+    match format {
+        Format::Ron => Ok(ron::de::from_str(s)?),
+        Format::Json => Ok(serde_json::from_str(s)?),
+        Format::Bin => bail!("not a text format"),
+    }
+}
+
+/// Read the header of a binary save without loading the planet.
+/// Returns `None` for text saves and legacy saves, which have no header.
+pub fn read_header<P: AsRef<Path>>(path: P) -> Result<Option<SaveHeader>> {
+    let path = path.as_ref();
+    if Format::from_path(path) != Format::Bin {
+        return Ok(None);
+    }
+    let mut r = open(path)?;
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Ok(None);
+    }
+    let (_, header) = read_header_after_magic(&mut r)?;
+    Ok(Some(header))
+}
+
+fn save_bin<W: Write>(planet: &Planet, w: W) -> Result<()> {
+    let mut w = GzEncoder::new(w, Compression::default());
+    w.write_all(&MAGIC)?;
+    bincode::serialize_into(&mut w, &FORMAT_VERSION)?;
+    bincode::serialize_into(&mut w, &SaveHeader::new(planet))?;
+    bincode::serialize_into(&mut w, planet)?;
+    w.finish()?;
+    Ok(())
+}
+
+/// Open a save file decompressing it if needed
+fn open(path: &Path) -> Result<Box<dyn Read>> {
+    let mut r = BufReader::new(std::fs::File::open(path)?);
+    if r.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(r))))
+    } else {
+        Ok(Box::new(r))
+    }
+}
+
+fn load_bin(path: &Path) -> Result<Planet> {
+    let mut r = open(path)?;
+
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        // Files saved before versioning have no header
+        let r = magic.chain(r);
+        let planet = bincode::deserialize_from(r).with_context(|| {
+            format!(
+                "{} is neither a save file nor a legacy save",
+                path.display()
+            )
+        })?;
+        return Ok(VersionedPlanet::V0(planet).migrate());
+    }
+
+    let (version, header) = read_header_after_magic(&mut r)?;
+    log::info!(
+        "loading {} saved by version {}",
+        path.display(),
+        header.game_version
+    );
+    let planet = match version {
+        1 => VersionedPlanet::V1(bincode::deserialize_from(r)?),
+        2 => VersionedPlanet::V2(bincode::deserialize_from(r)?),
+        3 => VersionedPlanet::V3(bincode::deserialize_from(r)?),
+        4 => VersionedPlanet::V4(bincode::deserialize_from(r)?),
+        _ => bail!("unsupported save version {}", version),
+    }
+    .migrate();
+
+    if planet.map.size() != header.map_size {
+        bail!(
+            "map size {:?} differs from {:?} in the header",
+            planet.map.size(),
+            header.map_size
+        );
+    }
+    Ok(planet)
+}
+
+fn read_header_after_magic<R: Read>(mut r: R) -> Result<(u32, SaveHeader)> {
+    let version: u32 = bincode::deserialize_from(&mut r)?;
+    if version == 0 || version > FORMAT_VERSION {
+        bail!(
+            "unsupported save format version {} (supported up to {})",
+            version,
+            FORMAT_VERSION
+        );
+    }
+    let header: SaveHeader = bincode::deserialize_from(&mut r)?;
+    Ok((version, header))
+}
+
+/// Layout before save files had a header
+mod v0 {
+    use geom::{Array2d, RectIter};
+    use rand::SeedableRng;
+    use serde::Deserialize;
+
+    use super::v1;
+    use super::v2::Biome;
+    use crate::defs::{LandFeature, Structure};
+    use crate::planet::{Atmosphere, Orbit, PlanetRng, Player};
+
+    #[derive(Deserialize)]
+    pub struct Tile {
+        pub biome: Biome,
+        pub land_feature: LandFeature,
+        pub structure: Structure,
+        pub biomass: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Planet {
+        pub tick: u64,
+        pub player: Player,
+        pub map: Array2d<Tile>,
+    }
+
+    impl From<Planet> for v1::Planet {
+        fn from(old: Planet) -> Self {
+            let (w, h) = old.map.size();
+            let mut map = Array2d::new(w, h, v1::Tile::default());
+            for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+                let old_tile = &old.map[p];
+                map[p] = v1::Tile {
+                    biome: old_tile.biome,
+                    land_feature: old_tile.land_feature.clone(),
+                    structure: old_tile.structure.clone(),
+                    biomass: old_tile.biomass,
+                    ..v1::Tile::default()
+                };
+            }
+
+            v1::Planet {
+                tick: old.tick,
+                player: old.player,
+                map,
+                atmo: Atmosphere::default(),
+                orbit: Orbit::default(),
+                disasters: Vec::new(),
+                rng: PlanetRng::from_entropy(),
+            }
+        }
+    }
+}
+
+/// Layout before structures had damage and building progress
+mod v1 {
+    use geom::{Array2d, RectIter};
+    use serde::Deserialize;
+
+    use super::v2::{self, Biome};
+    use crate::defs::{LandFeature, Structure};
+    use crate::planet::{Atmosphere, DisasterRecord, Orbit, PlanetRng, Player};
+
+    #[derive(Clone, Deserialize)]
+    pub struct Tile {
+        pub biome: Biome,
+        pub land_feature: LandFeature,
+        pub structure: Structure,
+        pub biomass: f32,
+        pub rainfall: f32,
+        pub temp: f32,
+        pub ice: bool,
+        pub sunlight: f32,
+        pub pollution: f32,
+        pub transition_ticks: u32,
+    }
+
+    impl Default for Tile {
+        fn default() -> Self {
+            Self {
+                biome: Biome::Ocean,
+                land_feature: LandFeature::None,
+                structure: Structure::None,
+                biomass: 0.0,
+                rainfall: 0.0,
+                temp: 0.0,
+                ice: false,
+                sunlight: 0.0,
+                pollution: 0.0,
+                transition_ticks: 0,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Planet {
+        pub tick: u64,
+        pub player: Player,
+        pub map: Array2d<Tile>,
+        pub atmo: Atmosphere,
+        pub orbit: Orbit,
+        pub disasters: Vec<DisasterRecord>,
+        pub rng: PlanetRng,
+    }
+
+    impl From<Planet> for v2::Planet {
+        fn from(old: Planet) -> Self {
+            let (w, h) = old.map.size();
+            let mut map = Array2d::new(w, h, v2::Tile::default());
+            for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+                let old_tile = &old.map[p];
+                map[p] = v2::Tile {
+                    biome: old_tile.biome,
+                    land_feature: old_tile.land_feature.clone(),
+                    structure: old_tile.structure.clone(),
+                    biomass: old_tile.biomass,
+                    rainfall: old_tile.rainfall,
+                    temp: old_tile.temp,
+                    ice: old_tile.ice,
+                    sunlight: old_tile.sunlight,
+                    pollution: old_tile.pollution,
+                    transition_ticks: old_tile.transition_ticks,
+                    damage: 0,
+                    build_ticks: 0,
+                };
+            }
+
+            v2::Planet {
+                tick: old.tick,
+                player: old.player,
+                map,
+                atmo: old.atmo,
+                orbit: old.orbit,
+                disasters: old.disasters,
+                rng: old.rng,
+            }
+        }
+    }
+}
+
+/// Layout before biomes were defined by the biome list
+mod v2 {
+    use geom::{Array2d, RectIter};
+    use serde::Deserialize;
+
+    use super::v3;
+    use crate::defs::{self, LandFeature, Structure};
+    use crate::planet::{self, Atmosphere, DisasterRecord, Orbit, PlanetRng, Player};
+
+    /// Biomes which were fixed in the game
+    #[derive(Clone, Copy, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Biome {
+        Ocean,
+        Mountains,
+        Desert,
+        Grassland,
+    }
+
+    /// Ids in the biome list, in the order of `Biome`
+    const BIOME_IDS: [&str; 4] = ["ocean", "mountains", "desert", "grassland"];
+
+    #[derive(Clone, Deserialize)]
+    pub struct Tile {
+        pub biome: Biome,
+        pub land_feature: LandFeature,
+        pub structure: Structure,
+        pub biomass: f32,
+        pub rainfall: f32,
+        pub temp: f32,
+        pub ice: bool,
+        pub sunlight: f32,
+        pub pollution: f32,
+        pub transition_ticks: u32,
+        pub damage: u32,
+        pub build_ticks: u32,
+    }
+
+    impl Default for Tile {
+        fn default() -> Self {
+            Self {
+                biome: Biome::Ocean,
+                land_feature: LandFeature::None,
+                structure: Structure::None,
+                biomass: 0.0,
+                rainfall: 0.0,
+                temp: 0.0,
+                ice: false,
+                sunlight: 0.0,
+                pollution: 0.0,
+                transition_ticks: 0,
+                damage: 0,
+                build_ticks: 0,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Planet {
+        pub tick: u64,
+        pub player: Player,
+        pub map: Array2d<Tile>,
+        pub atmo: Atmosphere,
+        pub orbit: Orbit,
+        pub disasters: Vec<DisasterRecord>,
+        pub rng: PlanetRng,
+    }
+
+    impl From<Planet> for v3::Planet {
+        fn from(old: Planet) -> Self {
+            let (w, h) = old.map.size();
+            let mut map = Array2d::new(w, h, planet::Tile::default());
+            for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+                let old_tile = &old.map[p];
+                map[p] = planet::Tile {
+                    biome: defs::Biome(old_tile.biome as u16),
+                    land_feature: old_tile.land_feature.clone(),
+                    structure: old_tile.structure.clone(),
+                    biomass: old_tile.biomass,
+                    rainfall: old_tile.rainfall,
+                    temp: old_tile.temp,
+                    ice: old_tile.ice,
+                    sunlight: old_tile.sunlight,
+                    pollution: old_tile.pollution,
+                    transition_ticks: old_tile.transition_ticks,
+                    damage: old_tile.damage,
+                    build_ticks: old_tile.build_ticks,
+                };
+            }
+
+            v3::Planet {
+                tick: old.tick,
+                biome_ids: BIOME_IDS.iter().map(|id| id.to_string()).collect(),
+                player: old.player,
+                map,
+                atmo: old.atmo,
+                orbit: old.orbit,
+                disasters: old.disasters,
+                rng: old.rng,
+            }
+        }
+    }
+}
+
+/// Layout before planets recorded active mods
+mod v3 {
+    use geom::Array2d;
+    use serde::Deserialize;
+
+    use crate::planet::{self, Atmosphere, DisasterRecord, Orbit, PlanetRng, Player, Tile};
+
+    #[derive(Deserialize)]
+    pub struct Planet {
+        pub tick: u64,
+        pub biome_ids: Vec<String>,
+        pub player: Player,
+        pub map: Array2d<Tile>,
+        pub atmo: Atmosphere,
+        pub orbit: Orbit,
+        pub disasters: Vec<DisasterRecord>,
+        pub rng: PlanetRng,
+    }
+
+    impl From<Planet> for planet::Planet {
+        fn from(old: Planet) -> Self {
+            planet::Planet {
+                tick: old.tick,
+                biome_ids: old.biome_ids,
+                mods: Vec::new(),
+                player: old.player,
+                map: old.map,
+                atmo: old.atmo,
+                orbit: old.orbit,
+                disasters: old.disasters,
+                rng: old.rng,
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{bail, Result};
+use fnv::FnvHashMap;
+use geom::{Coords, RectIter};
+use std::fmt;
+use std::path::Path;
+
+use crate::defs::{Biome, Structure, StructureKind, StructureSize};
+use crate::planet::{Atmosphere, Planet, MIN_MAP_SIZE};
+
+/// A problem found in a loaded planet
+#[derive(Clone, Debug)]
+pub enum Problem {
+    NotFinite {
+        name: &'static str,
+        count: usize,
+    },
+    /// Tiles whose biome is not in the biome table of the planet
+    InvalidBiome {
+        count: usize,
+    },
+    /// Tiles whose biome is not in the current biome list
+    MissingBiome {
+        id: String,
+        count: usize,
+    },
+    BrokenFootprint {
+        pos: Coords,
+    },
+    DanglingOccupied {
+        pos: Coords,
+        by: Coords,
+    },
+    NoCore,
+    ExtraCores {
+        count: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NotFinite { name, count } => {
+                write!(f, "{} non-finite value(s) of {}", count, name)
+            }
+            Problem::InvalidBiome { count } => write!(f, "{} tile(s) of invalid biome", count),
+            Problem::MissingBiome { id, count } => {
+                write!(f, "{} tile(s) of unknown biome \"{}\"", count, id)
+            }
+            Problem::BrokenFootprint { pos } => {
+                write!(f, "structure at {} does not occupy its footprint", pos)
+            }
+            Problem::DanglingOccupied { pos, by } => {
+                write!(f, "tile {} is occupied by missing structure at {}", pos, by)
+            }
+            Problem::NoCore => write!(f, "no core"),
+            Problem::ExtraCores { count } => write!(f, "{} cores", count),
+        }
+    }
+}
+
+/// Load a planet, validate it and renumber its biomes to follow `biome_ids`.
+/// Without `repair`, a planet with any problem is an error.
+pub fn load_checked<P: AsRef<Path>>(
+    path: P,
+    sizes: &FnvHashMap<StructureKind, StructureSize>,
+    biome_ids: &[String],
+    repair: bool,
+) -> Result<(Planet, Vec<Problem>)> {
+    let mut planet = super::save::load(path)?;
+    let mut problems = validate(&mut planet, sizes, repair)?;
+    problems.extend(
+        planet
+            .rebase_biomes(biome_ids)
+            .into_iter()
+            .map(|(id, count)| Problem::MissingBiome { id, count }),
+    );
+
+    if !repair && !problems.is_empty() {
+        bail!("broken planet: {}", join(&problems));
+    }
+    Ok((planet, problems))
+}
+
+pub fn join(problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(|problem| problem.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Check the consistency of a planet, and fix the problems if `repair` is true.
+/// Returns an error for problems which cannot be repaired.
+pub fn validate(
+    planet: &mut Planet,
+    sizes: &FnvHashMap<StructureKind, StructureSize>,
+    repair: bool,
+) -> Result<Vec<Problem>> {
+    let (w, h) = planet.map.size();
+    if w < MIN_MAP_SIZE || h < MIN_MAP_SIZE {
+        bail!("map size {}x{} is too small", w, h);
+    }
+
+    let mut problems = Vec::new();
+    check_values(planet, repair, &mut problems);
+    check_biomes(planet, repair, &mut problems);
+    check_structures(planet, sizes, repair, &mut problems);
+    check_core(planet, sizes, repair, &mut problems)?;
+    Ok(problems)
+}
+
+fn check_values(planet: &mut Planet, repair: bool, problems: &mut Vec<Problem>) {
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    let mut check = |name: &'static str, value: &mut f32, fallback: f32| {
+        if value.is_finite() {
+            return;
+        }
+        if repair {
+            *value = fallback;
+        }
+        if let Some((_, count)) = counts.iter_mut().find(|(n, _)| *n == name) {
+            *count += 1;
+        } else {
+            counts.push((name, 1));
+        }
+    };
+
+    check("energy", &mut planet.player.energy, 0.0);
+    check("material", &mut planet.player.material, 0.0);
+    check("co2", &mut planet.atmo.co2, Atmosphere::default().co2);
+
+    let (w, h) = planet.map.size();
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        check("biomass", &mut tile.biomass, 0.0);
+        check("rainfall", &mut tile.rainfall, 0.0);
+        check("temperature", &mut tile.temp, 0.0);
+        check("sunlight", &mut tile.sunlight, 0.0);
+        check("pollution", &mut tile.pollution, 0.0);
+    }
+
+    problems.extend(
+        counts
+            .into_iter()
+            .map(|(name, count)| Problem::NotFinite { name, count }),
+    );
+}
+
+fn check_biomes(planet: &mut Planet, repair: bool, problems: &mut Vec<Problem>) {
+    let n_biomes = planet.biome_ids.len();
+    let mut count = 0;
+    let (w, h) = planet.map.size();
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let tile = &mut planet.map[p];
+        if tile.biome.index() < n_biomes {
+            continue;
+        }
+        count += 1;
+        if repair {
+            tile.biome = Biome::default();
+        }
+    }
+
+    if count > 0 {
+        problems.push(Problem::InvalidBiome { count });
+    }
+}
+
+fn size_of(
+    structure: &Structure,
+    sizes: &FnvHashMap<StructureKind, StructureSize>,
+) -> StructureSize {
+    sizes
+        .get(&StructureKind::from(structure))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Whether the structure at `p` has `Occupied` on all its footprint tiles
+fn footprint_valid(planet: &Planet, p: Coords, size: StructureSize) -> bool {
+    size.occupied_tiles().into_iter().all(|p_rel| {
+        matches!(
+            planet.map.get(p + p_rel).map(|tile| &tile.structure),
+            Some(Structure::Occupied { by }) if *by == p
+        )
+    })
+}
+
+fn check_structures(
+    planet: &mut Planet,
+    sizes: &FnvHashMap<StructureKind, StructureSize>,
+    repair: bool,
+    problems: &mut Vec<Problem>,
+) {
+    let (w, h) = planet.map.size();
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let structure = &planet.map[p].structure;
+        if matches!(structure, Structure::None | Structure::Occupied { .. }) {
+            continue;
+        }
+        if !footprint_valid(planet, p, size_of(structure, sizes)) {
+            problems.push(Problem::BrokenFootprint { pos: p });
+            if repair {
+                planet.remove_structure(p);
+            }
+        }
+    }
+
+    for p in RectIter::new((0, 0), (w - 1, h - 1)) {
+        let by = if let Structure::Occupied { by } = planet.map[p].structure {
+            by
+        } else {
+            continue;
+        };
+        let covered = match planet.map.get(by).map(|tile| &tile.structure) {
+            None | Some(Structure::None) | Some(Structure::Occupied { .. }) => false,
+            Some(structure) => size_of(structure, sizes)
+                .occupied_tiles()
+                .into_iter()
+                .any(|p_rel| by + p_rel == p),
+        };
+        if !covered {
+            problems.push(Problem::DanglingOccupied { pos: p, by });
+            if repair {
+                planet.map[p].structure = Structure::None;
+            }
+        }
+    }
+}
+
+fn check_core(
+    planet: &mut Planet,
+    sizes: &FnvHashMap<StructureKind, StructureSize>,
+    repair: bool,
+    problems: &mut Vec<Problem>,
+) -> Result<()> {
+    let (w, h) = planet.map.size();
+    let cores: Vec<Coords> = RectIter::new((0, 0), (w - 1, h - 1))
+        .filter(|p| matches!(planet.map[*p].structure, Structure::Core))
+        .collect();
+
+    if cores.len() > 1 {
+        problems.push(Problem::ExtraCores { count: cores.len() });
+        if repair {
+            for p in &cores[1..] {
+                planet.remove_structure(*p);
+            }
+        }
+    } else if cores.is_empty() {
+        problems.push(Problem::NoCore);
+        if repair {
+            let size = size_of(&Structure::Core, sizes);
+            let p = RectIter::new((0, 0), (w - 1, h - 1))
+                .find(|p| {
+                    std::iter::once(Coords(0, 0))
+                        .chain(size.occupied_tiles())
+                        .all(|p_rel| {
+                            matches!(
+                                planet.map.get(*p + p_rel).map(|tile| &tile.structure),
+                                Some(Structure::None)
+                            )
+                        })
+                })
+                .ok_or_else(|| anyhow::anyhow!("no space to place the core"))?;
+            planet.map[p].structure = Structure::Core;
+            for p_rel in size.occupied_tiles() {
+                planet.map[p + p_rel].structure = Structure::Occupied { by: p };
+            }
+        }
+    }
+    Ok(())
+}
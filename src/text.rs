@@ -1,10 +1,11 @@
 use bevy::asset::LoadState;
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use regex::{Captures, Regex};
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 
 macro_rules! t {
@@ -24,7 +25,7 @@ macro_rules! t {
 
 static TRANSLATION_TEXTS: Lazy<RwLock<HashMap<String, TranslationText>>> =
     Lazy::new(|| RwLock::new(HashMap::default()));
-static LANG_CODE: Lazy<String> = Lazy::new(lang_code);
+static LANG_CODE: OnceCell<String> = OnceCell::new();
 
 #[derive(Clone, Copy, Debug)]
 pub struct TextPlugin;
@@ -34,6 +35,12 @@ pub struct TextPlugin;
 #[uuid = "c5967cb0-5b5a-433e-b659-8a96ff47422f"]
 pub struct TranslationText(HashMap<String, String>);
 
+impl TranslationText {
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|key| key.as_str())
+    }
+}
+
 impl Plugin for TextPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(bevy_asset_ron::RonAssetPlugin::<TranslationText>::new(&[
@@ -41,13 +48,19 @@ impl Plugin for TextPlugin {
         ]))
         .init_resource::<TextLoading>()
         .add_startup_system(load_text)
-        .add_system(update_text);
+        .add_system(update_text)
+        .add_system(reload_text);
     }
 }
 
 #[derive(Default)]
 struct TextLoading(Vec<HandleUntyped>);
 
+/// Handles kept after loading, to be notified of modifications
+struct TextHandles {
+    _handles: Vec<HandleUntyped>,
+}
+
 fn load_text(asset_server: Res<AssetServer>, mut text_loading: ResMut<TextLoading>) {
     text_loading
         .0
@@ -67,35 +80,20 @@ fn update_text(
     };
     match asset_server.get_group_load_state(loading.0.iter().map(|h| h.id)) {
         LoadState::Failed => {
-            panic!();
+            log::error!("cannot load translation texts");
+            command.remove_resource::<TextLoading>();
+            return;
         }
         LoadState::Loaded => (),
         _ => {
             return;
         }
     }
+    command.insert_resource(TextHandles {
+        _handles: loading.0.clone(),
+    });
 
-    let texts = texts
-        .iter()
-        .map(|(id, text)| {
-            (
-                asset_server
-                    .get_handle_path(id)
-                    .unwrap()
-                    .path()
-                    .display()
-                    .to_string()
-                    .strip_prefix("text/")
-                    .unwrap()
-                    .strip_suffix(".text.ron")
-                    .unwrap()
-                    .to_string(),
-                text.clone(),
-            )
-        })
-        .collect::<HashMap<String, TranslationText>>();
-
-    *TRANSLATION_TEXTS.write().unwrap() = texts;
+    set_texts(&asset_server, &texts);
 
     crate::msg::push_msg(
         crate::msg::MsgKind::Notice,
@@ -105,6 +103,76 @@ fn update_text(
     command.remove_resource::<TextLoading>();
 }
 
+/// Update texts when translation files are modified
+fn reload_text(
+    asset_server: Res<AssetServer>,
+    loading: Option<Res<TextLoading>>,
+    mut er_texts: EventReader<AssetEvent<TranslationText>>,
+    texts: Res<Assets<TranslationText>>,
+) {
+    let modified = er_texts
+        .iter()
+        .any(|e| matches!(e, AssetEvent::Modified { .. }));
+    if loading.is_some() || !modified {
+        return;
+    }
+    set_texts(&asset_server, &texts);
+    log::info!("translation texts reloaded");
+}
+
+/// Merge translation files by language in the order of layers.
+/// Files named `<lang>.text.ron` or `<lang>.<name>.text.ron` have texts of `<lang>`.
+fn set_texts(asset_server: &AssetServer, texts: &Assets<TranslationText>) {
+    let mut files: Vec<(PathBuf, &TranslationText)> = texts
+        .iter()
+        .filter_map(|(id, text)| {
+            asset_server
+                .get_handle_path(id)
+                .map(|path| (path.path().to_owned(), text))
+        })
+        .collect();
+    crate::mods::sort_by_layer(&mut files, |(path, _)| path.as_path());
+
+    let mut merged: HashMap<String, TranslationText> = HashMap::default();
+    for (path, text) in files {
+        let lang = if let Some(lang) = lang_of(&path) {
+            lang
+        } else {
+            continue;
+        };
+        merged
+            .entry(lang)
+            .or_default()
+            .0
+            .extend(text.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    *TRANSLATION_TEXTS.write().unwrap() = merged;
+}
+
+/// Language code of a translation file
+pub fn lang_of(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(".text.ron")?
+        .split('.')
+        .next()
+        .map(|lang| lang.to_owned())
+}
+
+/// Use the given language instead of the environment's one.
+/// Must be called before getting any text.
+pub fn set_lang_code(lang: &str) -> anyhow::Result<()> {
+    if !crate::mods::list_files("text", ".text.ron")
+        .iter()
+        .any(|path| lang_of(path).as_deref() == Some(lang))
+    {
+        anyhow::bail!("unsupported language \"{}\"", lang);
+    }
+    LANG_CODE
+        .set(lang.into())
+        .map_err(|_| anyhow::anyhow!("language is already set"))
+}
+
 fn lang_code() -> String {
     if let Ok(lang) = std::env::var("LANG") {
         if let Some(lang) = lang.split('_').next() {
@@ -115,7 +183,11 @@ fn lang_code() -> String {
 }
 
 pub fn get_text(s: &str, map: HashMap<String, String>) -> String {
-    if let Some(translation_text) = TRANSLATION_TEXTS.read().unwrap().get(&*LANG_CODE) {
+    if let Some(translation_text) = TRANSLATION_TEXTS
+        .read()
+        .unwrap()
+        .get(LANG_CODE.get_or_init(lang_code))
+    {
         if let Some(text) = translation_text.0.get(s) {
             if map.is_empty() {
                 return text.into();
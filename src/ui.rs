@@ -1,3 +1,6 @@
+mod saves;
+mod timeline;
+
 use bevy::{
     app::AppExit,
     input::{keyboard::KeyboardInput, ElementState},
@@ -9,9 +12,9 @@ use bevy_egui::{
 };
 use std::collections::{HashMap, VecDeque};
 
-use crate::{defs::StructureKind, planet::Planet};
+use crate::{assets::AssetsLoaded, defs::StructureKind, planet::Planet};
 use crate::{
-    defs::{Biome, Structure},
+    defs::{Biome, Overlay, Structure},
     screen::{CursorMode, HoverTile, OccupiedScreenSpace},
 };
 use crate::{msg::MsgKind, sim::ManagePlanet};
@@ -25,6 +28,8 @@ pub struct UiPlugin {
 pub struct WindowsOpenState {
     edit_map: bool,
     build: bool,
+    saves: bool,
+    timeline: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -64,6 +69,8 @@ impl Plugin for UiPlugin {
             .add_system(msg_window.label("ui_windows"))
             .add_system(build_window.label("ui_windows"))
             .add_system(edit_map_window.label("ui_windows"))
+            .add_system(saves::save_window.label("ui_windows"))
+            .add_system(timeline::timeline_window.label("ui_windows"))
             .add_system(exit_on_esc_system);
     }
 }
@@ -146,7 +153,9 @@ fn panels(
     hover_tile: Query<&HoverTile>,
     mut cursor_mode: ResMut<CursorMode>,
     mut wos: ResMut<WindowsOpenState>,
+    mut overlay: ResMut<Overlay>,
     planet: Res<Planet>,
+    assets: Option<Res<AssetsLoaded>>,
     textures: Res<UiTextures>,
     conf: Res<UiConf>,
 ) {
@@ -155,7 +164,13 @@ fn panels(
     occupied_screen_space.occupied_left = egui::SidePanel::left("left_panel")
         .resizable(true)
         .show(egui_ctx.ctx_mut(), |ui| {
-            sidebar(ui, &cursor_mode, &planet, hover_tile.get_single().unwrap());
+            sidebar(
+                ui,
+                &cursor_mode,
+                &planet,
+                assets.as_deref(),
+                hover_tile.get_single().unwrap(),
+            );
             ui.allocate_rect(ui.available_rect_before_wrap(), egui::Sense::hover());
         })
         .response
@@ -167,7 +182,14 @@ fn panels(
         .resizable(false)
         .show(egui_ctx.ctx_mut(), |ui| {
             ui.horizontal(|ui| {
-                toolbar(ui, &mut cursor_mode, &mut wos, &textures, &conf);
+                toolbar(
+                    ui,
+                    &mut cursor_mode,
+                    &mut wos,
+                    &mut overlay,
+                    &textures,
+                    &conf,
+                );
             });
             ui.allocate_rect(ui.available_rect_before_wrap(), egui::Sense::hover());
         })
@@ -177,9 +199,21 @@ fn panels(
         * conf.scale_factor;
 }
 
-fn sidebar(ui: &mut egui::Ui, cursor_mode: &CursorMode, planet: &Planet, hover_tile: &HoverTile) {
+fn sidebar(
+    ui: &mut egui::Ui,
+    cursor_mode: &CursorMode,
+    planet: &Planet,
+    assets: Option<&AssetsLoaded>,
+    hover_tile: &HoverTile,
+) {
+    let date = planet.date();
+    ui.label(t!("date"; year = date.year, season = t!(date.season.as_ref()), day = date.day));
+
+    ui.separator();
+
     ui.label(&format!("{}: {}", t!("energy"), planet.player.energy));
     ui.label(&format!("{}: {}", t!("material"), planet.player.material));
+    ui.label(format!("{}: {:.1} ppm", t!("co2"), planet.atmo.co2));
 
     ui.separator();
 
@@ -189,14 +223,13 @@ fn sidebar(ui: &mut egui::Ui, cursor_mode: &CursorMode, planet: &Planet, hover_t
         CursorMode::Normal => {
             ui.label(t!("none"));
         }
-        CursorMode::Build(kind) => match kind {
-            StructureKind::Branch => {
-                ui.label(t!("branch"));
+        CursorMode::Build(kind) => {
+            if let Some(asset) = assets.and_then(|assets| assets.structures.get(kind)) {
+                ui.label(crate::info::structure_name(*kind, &asset.attrs));
             }
-            _ => unreachable!(),
-        },
+        }
         CursorMode::EditBiome(biome) => {
-            ui.label(format!("biome editing: {}", biome.as_ref()));
+            ui.label(format!("biome editing: {}", t!(planet.biome_id(*biome))));
         }
     }
 
@@ -206,17 +239,33 @@ fn sidebar(ui: &mut egui::Ui, cursor_mode: &CursorMode, planet: &Planet, hover_t
     if let Some(p) = hover_tile.0 {
         ui.label(format!("{}: [{}, {}]", t!("coordinates"), p.0, p.1));
         let tile = &planet.map[p];
+        ui.label(format!(
+            "{}: {}",
+            t!("biome"),
+            t!(planet.biome_id(tile.biome))
+        ));
+        ui.label(format!("{}: {:.1} °C", t!("temperature"), tile.temp));
+        ui.label(format!("{}: {:.0} mm", t!("rainfall"), tile.rainfall));
+        ui.label(format!(
+            "{}: {:.0} %",
+            t!("sunlight"),
+            tile.sunlight * 100.0
+        ));
+        ui.label(format!("{}: {:.2} kg/m²", t!("biomass"), tile.biomass));
+        if tile.ice {
+            ui.label(t!("ice"));
+        }
 
-        let s = match &tile.structure {
+        let base = match &tile.structure {
             Structure::None => None,
-            Structure::Occupied { by } => {
-                Some(crate::info::structure_info(&planet.map[*by].structure))
-            }
-            other => Some(crate::info::structure_info(other)),
+            Structure::Occupied { by } => Some(*by),
+            _ => Some(p),
         };
-
-        if let Some(s) = s {
-            ui.label(s);
+        if let (Some(base_tile), Some(assets)) = (base.and_then(|p| planet.map.get(p)), assets) {
+            let kind = StructureKind::from(&base_tile.structure);
+            if let Some(asset) = assets.structures.get(&kind) {
+                ui.label(crate::info::structure_info(kind, &asset.attrs, base_tile));
+            }
         }
     } else {
         ui.label(format!("{}: -", t!("coordinates")));
@@ -227,6 +276,7 @@ fn toolbar(
     ui: &mut egui::Ui,
     cursor_mode: &mut CursorMode,
     wos: &mut WindowsOpenState,
+    overlay: &mut Overlay,
     textures: &UiTextures,
     conf: &UiConf,
 ) {
@@ -252,23 +302,73 @@ fn toolbar(
             wos.build = true;
         };
     }
+    if ui.button(t!("saves")).clicked() {
+        wos.saves = true;
+    }
+    if ui.button(t!("timeline")).clicked() {
+        wos.timeline = true;
+    }
+
+    ui.separator();
+    ui.label(t!("overlay"));
+    egui::ComboBox::from_id_source("overlay")
+        .selected_text(t!(overlay.as_ref()))
+        .show_ui(ui, |ui| {
+            use strum::IntoEnumIterator;
+            for o in Overlay::iter() {
+                ui.selectable_value(overlay, o, t!(o.as_ref()));
+            }
+        });
 }
 
 fn build_window(
     mut egui_ctx: ResMut<EguiContext>,
     mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
     mut wos: ResMut<WindowsOpenState>,
+    mut cursor_mode: ResMut<CursorMode>,
+    assets: Option<Res<AssetsLoaded>>,
     conf: Res<UiConf>,
 ) {
     if !wos.build {
         return;
     }
+    let assets = if let Some(assets) = &assets {
+        assets
+    } else {
+        return;
+    };
 
-    let rect = egui::Window::new("Select an item to build")
+    let rect = egui::Window::new(t!("build"))
         .open(&mut wos.build)
         .vscroll(true)
         .show(egui_ctx.ctx_mut(), |ui| {
-            ui.label("工事中");
+            use strum::IntoEnumIterator;
+            for kind in StructureKind::iter() {
+                let attrs = if let Some(asset) = assets.structures.get(&kind) {
+                    &asset.attrs
+                } else {
+                    continue;
+                };
+                if !attrs.buildable {
+                    continue;
+                }
+                ui.horizontal(|ui| {
+                    let mut button = ui.button(crate::info::structure_name(kind, attrs));
+                    if let Some(description) = &attrs.description {
+                        button = button.on_hover_text(t!(description));
+                    }
+                    if button.clicked() {
+                        *cursor_mode = CursorMode::Build(kind);
+                    }
+                    ui.label(format!(
+                        "{}: {}  {}: {}",
+                        t!("energy"),
+                        attrs.cost.energy,
+                        t!("material"),
+                        attrs.cost.material
+                    ));
+                });
+            }
         })
         .unwrap()
         .response
@@ -314,9 +414,16 @@ fn edit_map_window(
     wos: Res<WindowsOpenState>,
     conf: Res<UiConf>,
     mut ew_manage_planet: EventWriter<ManagePlanet>,
+    planet: Res<Planet>,
     (mut new_w, mut new_h): (Local<u32>, Local<u32>),
     mut biome: Local<Biome>,
     mut save_file_path: Local<String>,
+    (mut biome_png_path, mut land_feature_png_path, mut export_png_path): (
+        Local<String>,
+        Local<String>,
+        Local<String>,
+    ),
+    mut export_layer: Local<Overlay>,
 ) {
     if !wos.edit_map {
         return;
@@ -334,12 +441,11 @@ fn edit_map_window(
             });
 
             ui.horizontal(|ui| {
-                egui::ComboBox::from_id_source(Biome::Ocean)
-                    .selected_text(AsRef::<str>::as_ref(&*biome))
+                egui::ComboBox::from_id_source("edit-biome")
+                    .selected_text(t!(planet.biome_id(*biome)))
                     .show_ui(ui, |ui| {
-                        use strum::IntoEnumIterator;
-                        for b in Biome::iter() {
-                            ui.selectable_value(&mut *biome, b, AsRef::<str>::as_ref(&b));
+                        for (i, id) in planet.biome_ids.iter().enumerate() {
+                            ui.selectable_value(&mut *biome, Biome(i as u16), t!(id));
                         }
                     });
                 if ui.button("Edit biome").clicked()
@@ -359,6 +465,51 @@ fn edit_map_window(
                     ew_manage_planet.send(ManagePlanet::Load(save_file_path.clone()));
                 }
             });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Biome PNG");
+                ui.add(egui::TextEdit::singleline(&mut *biome_png_path));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Land feature PNG");
+                ui.add(egui::TextEdit::singleline(&mut *land_feature_png_path));
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Import").clicked() {
+                    let land_feature = if land_feature_png_path.is_empty() {
+                        None
+                    } else {
+                        Some(land_feature_png_path.clone())
+                    };
+                    ew_manage_planet.send(ManagePlanet::ImportPng {
+                        biome: biome_png_path.clone(),
+                        land_feature,
+                    });
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Export PNG");
+                ui.add(egui::TextEdit::singleline(&mut *export_png_path));
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("export_layer")
+                    .selected_text(layer_name(&export_layer))
+                    .show_ui(ui, |ui| {
+                        use strum::IntoEnumIterator;
+                        for layer in Overlay::iter() {
+                            ui.selectable_value(&mut *export_layer, layer, layer_name(&layer));
+                        }
+                    });
+                if ui.button("Export").clicked() {
+                    ew_manage_planet.send(ManagePlanet::ExportPng(
+                        export_png_path.clone(),
+                        *export_layer,
+                    ));
+                }
+            });
         })
         .unwrap()
         .response
@@ -368,6 +519,13 @@ fn edit_map_window(
         .push(convert_rect(rect, conf.scale_factor));
 }
 
+fn layer_name(layer: &Overlay) -> &str {
+    match layer {
+        Overlay::None => "biome",
+        _ => layer.as_ref(),
+    }
+}
+
 fn convert_rect(rect: bevy_egui::egui::Rect, scale_factor: f32) -> bevy::math::Rect<f32> {
     bevy::math::Rect {
         top: rect.top() * scale_factor,
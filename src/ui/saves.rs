@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy_egui::{egui, EguiContext};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::{convert_rect, UiConf, WindowsOpenState};
+use crate::assets::AssetsLoaded;
+use crate::defs::Overlay;
+use crate::planet::{Date, Planet};
+use crate::screen::OccupiedScreenSpace;
+use crate::sim::{save, ManagePlanet, SaveConf};
+
+/// Max width and height of thumbnails in pixels
+const THUMBNAIL_SIZE: f32 = 64.0;
+
+#[derive(Default)]
+pub struct SaveWindowState {
+    opened: bool,
+    needs_refresh: bool,
+    entries: Vec<SaveEntry>,
+    /// Details loaded in the background, kept while the files are not modified
+    details: HashMap<PathBuf, (SystemTime, Option<Result<SaveDetails, String>>)>,
+    /// Details sent from loading tasks
+    loaded: Arc<Mutex<Vec<LoadedDetails>>>,
+    name: String,
+    confirm: Option<Confirm>,
+}
+
+struct SaveEntry {
+    path: PathBuf,
+    name: String,
+    modified: SystemTime,
+    /// Read from the header, `None` for saves without a header
+    header: Option<Result<save::SaveHeader, String>>,
+}
+
+struct SaveDetails {
+    tick: u64,
+    date: Date,
+    map_size: (u32, u32),
+    thumbnail: Option<egui::TextureHandle>,
+}
+
+struct LoadedDetails {
+    path: PathBuf,
+    modified: SystemTime,
+    /// Details without the thumbnail texture, and the thumbnail image
+    result: Result<(SaveDetails, Option<egui::ColorImage>), String>,
+}
+
+enum Confirm {
+    Overwrite(PathBuf),
+    Delete(PathBuf),
+}
+
+pub fn save_window(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
+    mut wos: ResMut<WindowsOpenState>,
+    conf: Res<UiConf>,
+    save_conf: Res<SaveConf>,
+    assets: Option<Res<AssetsLoaded>>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    mut ew_manage_planet: EventWriter<ManagePlanet>,
+    mut state: Local<SaveWindowState>,
+) {
+    if !wos.saves {
+        state.opened = false;
+        return;
+    }
+    let state = &mut *state;
+    if !state.opened || state.needs_refresh {
+        state.opened = true;
+        state.needs_refresh = false;
+        state.entries = list_saves(&save_conf.dir);
+        load_details(state, assets.as_deref(), &task_pool);
+    }
+    receive_details(state, egui_ctx.ctx_mut());
+
+    let rect = egui::Window::new(t!("saves"))
+        .open(&mut wos.saves)
+        .vscroll(true)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            if let Some(confirm) = state.confirm.take() {
+                let (msg, path) = match &confirm {
+                    Confirm::Overwrite(path) => (t!("overwrite-confirm"), path),
+                    Confirm::Delete(path) => (t!("delete-confirm"), path),
+                };
+                ui.label(format!("{}: {}", msg, file_name(path)));
+
+                let mut answered = false;
+                ui.horizontal(|ui| {
+                    if ui.button(t!("yes")).clicked() {
+                        match &confirm {
+                            Confirm::Overwrite(path) => {
+                                ew_manage_planet.send(ManagePlanet::Save(path_string(path)));
+                            }
+                            Confirm::Delete(path) => {
+                                if let Err(e) = std::fs::remove_file(path) {
+                                    log::warn!("cannot delete {}: {:?}", path.display(), e);
+                                }
+                            }
+                        }
+                        answered = true;
+                        state.needs_refresh = true;
+                    }
+                    if ui.button(t!("no")).clicked() {
+                        answered = true;
+                    }
+                });
+                if !answered {
+                    state.confirm = Some(confirm);
+                }
+                ui.separator();
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut state.name));
+                let valid_name = !state.name.is_empty()
+                    && !state.name.contains(std::path::is_separator)
+                    && state.confirm.is_none();
+                if ui
+                    .add_enabled(valid_name, egui::Button::new(t!("save")))
+                    .clicked()
+                {
+                    let path = save_path(&save_conf.dir, &state.name);
+                    if path.exists() {
+                        state.confirm = Some(Confirm::Overwrite(path));
+                    } else if let Err(e) = std::fs::create_dir_all(&save_conf.dir) {
+                        log::warn!("cannot create {}: {:?}", save_conf.dir.display(), e);
+                    } else {
+                        ew_manage_planet.send(ManagePlanet::Save(path_string(&path)));
+                        state.needs_refresh = true;
+                    }
+                }
+                if ui.button(t!("refresh")).clicked() {
+                    state.needs_refresh = true;
+                }
+            });
+            ui.separator();
+
+            for entry in &state.entries {
+                let details = match state.details.get(&entry.path) {
+                    Some((modified, Some(details))) if *modified == entry.modified => Some(details),
+                    _ => None,
+                };
+                let error = match (&entry.header, details) {
+                    (Some(Err(e)), _) | (_, Some(Err(e))) => Some(e),
+                    _ => None,
+                };
+
+                ui.horizontal(|ui| {
+                    if let Some(Ok(SaveDetails {
+                        thumbnail: Some(thumbnail),
+                        map_size,
+                        ..
+                    })) = details
+                    {
+                        let scale = THUMBNAIL_SIZE / map_size.0.max(map_size.1) as f32;
+                        ui.image(
+                            thumbnail.id(),
+                            egui::Vec2::new(map_size.0 as f32, map_size.1 as f32) * scale,
+                        );
+                    }
+
+                    ui.vertical(|ui| {
+                        ui.label(&entry.name);
+                        if let Some(e) = error {
+                            ui.label(e);
+                        } else {
+                            if let Some(Ok(details)) = details {
+                                ui.label(t!("date";
+                                    year = details.date.year,
+                                    season = t!(details.date.season.as_ref()),
+                                    day = details.date.day));
+                            }
+                            let tick_and_size = match (details, &entry.header) {
+                                (Some(Ok(details)), _) => Some((details.tick, details.map_size)),
+                                (_, Some(Ok(header))) => Some((header.tick, header.map_size)),
+                                _ => None,
+                            };
+                            if let Some((tick, map_size)) = tick_and_size {
+                                ui.label(format!(
+                                    "{}: {}  {}: {}x{}",
+                                    t!("tick"),
+                                    tick,
+                                    t!("map-size"),
+                                    map_size.0,
+                                    map_size.1
+                                ));
+                            }
+                        }
+                        ui.label(
+                            chrono::DateTime::<chrono::Local>::from(entry.modified)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string(),
+                        );
+
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(error.is_none(), egui::Button::new(t!("load")))
+                                .clicked()
+                            {
+                                ew_manage_planet.send(ManagePlanet::Load(path_string(&entry.path)));
+                            }
+                            if ui.button(t!("delete")).clicked() {
+                                state.confirm = Some(Confirm::Delete(entry.path.clone()));
+                            }
+                        });
+                    });
+                });
+                ui.separator();
+            }
+        })
+        .unwrap()
+        .response
+        .rect;
+    occupied_screen_space
+        .window_rects
+        .push(convert_rect(rect, conf.scale_factor));
+}
+
+/// List save files with their headers, which are cheap to read
+fn list_saves(dir: &Path) -> Vec<SaveEntry> {
+    let read_dir = if let Ok(read_dir) = std::fs::read_dir(dir) {
+        read_dir
+    } else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<SaveEntry> = read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| save::has_save_extension(path))
+        .map(|path| {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            let header = save::read_header(&path)
+                .map_err(|e| format!("{:#}", e))
+                .transpose();
+            SaveEntry {
+                name: file_name(&path),
+                path,
+                modified,
+                header,
+            }
+        })
+        .collect();
+
+    // Newest first
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.modified));
+    entries
+}
+
+/// Start loading details of the listed saves which are new or modified, in the background
+fn load_details(
+    state: &mut SaveWindowState,
+    assets: Option<&AssetsLoaded>,
+    task_pool: &AsyncComputeTaskPool,
+) {
+    let biome_colors = assets.map(|assets| assets.biome_colors());
+
+    for entry in &state.entries {
+        if matches!(entry.header, Some(Err(_)))
+            || matches!(state.details.get(&entry.path), Some((modified, _)) if *modified == entry.modified)
+        {
+            continue;
+        }
+        state
+            .details
+            .insert(entry.path.clone(), (entry.modified, None));
+
+        let path = entry.path.clone();
+        let modified = entry.modified;
+        let biome_colors = biome_colors.clone();
+        let loaded = state.loaded.clone();
+        task_pool
+            .spawn(async move {
+                let result = save::load(&path)
+                    .map(|planet| {
+                        let thumbnail = biome_colors
+                            .map(|biome_colors| thumbnail_image(&planet, &biome_colors));
+                        let details = SaveDetails {
+                            tick: planet.tick,
+                            date: planet.date(),
+                            map_size: planet.map.size(),
+                            thumbnail: None,
+                        };
+                        (details, thumbnail)
+                    })
+                    .map_err(|e| format!("{:#}", e));
+                loaded.lock().unwrap().push(LoadedDetails {
+                    path,
+                    modified,
+                    result,
+                });
+            })
+            .detach();
+    }
+}
+
+/// Store details sent from loading tasks, creating thumbnail textures
+fn receive_details(state: &mut SaveWindowState, ctx: &egui::Context) {
+    let loaded = std::mem::take(&mut *state.loaded.lock().unwrap());
+    for LoadedDetails {
+        path,
+        modified,
+        result,
+    } in loaded
+    {
+        let details = result.map(|(details, thumbnail)| SaveDetails {
+            thumbnail: thumbnail.map(|image| ctx.load_texture(path.display().to_string(), image)),
+            ..details
+        });
+        state.details.insert(path, (modified, Some(details)));
+    }
+}
+
+fn thumbnail_image(
+    planet: &Planet,
+    biome_colors: &crate::map_image::BiomeColors,
+) -> egui::ColorImage {
+    let (w, h) = planet.map.size();
+    let pixels = crate::map_image::layer_pixels(planet, Overlay::None, biome_colors)
+        .into_iter()
+        .map(|[r, g, b]| egui::Color32::from_rgb(r, g, b))
+        .collect();
+
+    egui::ColorImage {
+        size: [w as usize, h as usize],
+        pixels,
+    }
+}
+
+/// Names without a save extension are saved in the default format
+fn save_path(dir: &Path, name: &str) -> PathBuf {
+    if save::has_save_extension(Path::new(name)) {
+        dir.join(name)
+    } else {
+        dir.join(format!("{}.bin", name))
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
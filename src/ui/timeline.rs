@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use super::{convert_rect, UiConf, WindowsOpenState};
+use crate::planet::Planet;
+use crate::screen::OccupiedScreenSpace;
+use crate::sim::history::History;
+use crate::sim::ManagePlanet;
+
+pub fn timeline_window(
+    mut egui_ctx: ResMut<EguiContext>,
+    mut occupied_screen_space: ResMut<OccupiedScreenSpace>,
+    mut wos: ResMut<WindowsOpenState>,
+    conf: Res<UiConf>,
+    planet: Res<Planet>,
+    mut history: ResMut<History>,
+    mut ew_manage_planet: EventWriter<ManagePlanet>,
+) {
+    // The simulation stops while this window is open
+    if history.rewinding != wos.timeline {
+        history.rewinding = wos.timeline;
+    }
+    if !wos.timeline {
+        return;
+    }
+
+    let ticks: Vec<u64> = history.ticks().collect();
+    let rect = egui::Window::new(t!("timeline"))
+        .open(&mut wos.timeline)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            if ticks.is_empty() {
+                ui.label(t!("no-snapshots"));
+                return;
+            }
+
+            let mut i = ticks
+                .iter()
+                .position(|tick| *tick >= planet.tick)
+                .unwrap_or(ticks.len() - 1);
+            if ui
+                .add(egui::Slider::new(&mut i, 0..=(ticks.len() - 1)).show_value(false))
+                .changed()
+            {
+                ew_manage_planet.send(ManagePlanet::Rewind(ticks[i]));
+            }
+
+            let date = planet.date();
+            ui.label(t!("date";
+                year = date.year,
+                season = t!(date.season.as_ref()),
+                day = date.day));
+            ui.label(format!("{}: {}", t!("tick"), planet.tick));
+            ui.label(t!("timeline-help"));
+        })
+        .unwrap()
+        .response
+        .rect;
+    occupied_screen_space
+        .window_rects
+        .push(convert_rect(rect, conf.scale_factor));
+}